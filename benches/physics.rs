@@ -0,0 +1,56 @@
+//! Benchmarks for the cube simulation.
+//! Only the headless game logic is exercised; nothing here touches a window or GL.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+use cgmath::{Point3, Quaternion, Rad, Rotation3, Vector3};
+use collision::Ray3;
+
+use cubes::game::GameState;
+use cubes::game::cube::Cube;
+
+/// Explode every subcube `times` times over, giving `8^times` subcubes.
+fn shattered_cube(times: u32) -> Cube {
+    let mut cube = Cube::new();
+    for _ in 0..times {
+        for index in 0..cube.subcubes.len() {
+            cube.explode_subcube(index, 4.0, 2);
+        }
+    }
+    cube
+}
+
+fn bench_step(c: &mut Criterion) {
+    let frac = 1.0 / GameState::steps_per_second() as f32;
+
+    c.bench_function("step 4096 subcubes", |b| {
+        b.iter_batched_ref(
+            || shattered_cube(4),
+            |cube| cube.step(black_box(frac)),
+            criterion::BatchSize::LargeInput
+        )
+    });
+}
+
+fn bench_get_subcube_from_ray(c: &mut Criterion) {
+    let ray = Ray3::new(Point3::new(0.25, 0.25, 5.0), Vector3::new(0.0, 0.0, -1.0));
+
+    // Fresh shards haven't turned, so they take the unrotated fast path.
+    // Turning some of them shows what the general path costs.
+    for (name, rotated_every) in [("unrotated", None), ("half rotated", Some(2)), ("rotated", Some(1))] {
+        let mut cube = shattered_cube(4);
+        if let Some(n) = rotated_every {
+            for subcube in cube.subcubes.iter_mut().step_by(n) {
+                subcube.orientation = Quaternion::from_angle_y(Rad(0.3));
+            }
+        }
+
+        c.bench_function(&format!("get_subcube_from_ray 4096 subcubes, {}", name), |b| {
+            b.iter(|| cube.get_subcube_from_ray(black_box(&ray)).map(|(index, _)| index))
+        });
+    }
+}
+
+criterion_group!(benches, bench_step, bench_get_subcube_from_ray);
+criterion_main!(benches);
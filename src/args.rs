@@ -0,0 +1,205 @@
+//! Command-line arguments for the demo binary.
+
+use crate::VSyncMode;
+
+pub const USAGE: &str = "\
+Usage: rust-cubes-demo [options]
+
+Options:
+    --width N          Window width in pixels (default 1920)
+    --height N         Window height in pixels (default 1080)
+    --fullscreen       Start fullscreen
+    --vsync MODE       off, on or adaptive (default: CUBES_VSYNC, or the driver's choice)
+    --subdivide N      Start with the cube split N ways on each axis (default 1)
+    --seed N           Seed the random numbers used when hurling subcubes
+    --demo             Run the scripted demo reel
+    --attract N        Play by itself after N seconds without input
+    --idle-spin        Slowly spin the cube while nobody's turning it
+    --render-to FILE   Save the first frame to FILE as a PNG, then exit
+    --render-steps N   Run N steps before that frame (default 1)
+    --frame-budget MS  Explode more coarsely while frames take longer than MS
+    --trails FADE      Leave trails, clearing FADE of each frame, from 0 to 1
+    --fuse SECONDS     Explode clicked subcubes after SECONDS, flashing until then
+    --edge-darkening AMOUNT
+                       Darken the edges of each face by AMOUNT, from 0 to 1
+    --srgb             Treat colors as linear, and encode them as sRGB to draw them
+    --sim-log FILE     Write the subcube count, energy and spread of every step to FILE as CSV
+    --verbose          Print the subcube count and cube state when they change,
+                       any time skipped after a stall, and frame time percentiles
+    --help             Print this message";
+
+pub struct Args {
+    pub width: i32,
+    pub height: i32,
+    pub fullscreen: bool,
+    pub vsync: Option<VSyncMode>,
+    pub subdivide: u32,
+    pub seed: Option<u64>,
+    pub demo: bool,
+    pub attract: Option<f32>,
+    pub idle_spin: bool,
+    pub render_to: Option<String>,
+    pub render_steps: u32,
+    pub frame_budget: Option<f64>,
+    pub trails: Option<f32>,
+    pub fuse: Option<f32>,
+    pub sim_log: Option<String>,
+    pub edge_darkening: Option<f32>,
+    pub srgb: bool,
+    pub verbose: bool,
+    pub help: bool
+}
+
+impl Default for Args {
+    fn default() -> Args {
+        Args {
+            width: 1920,
+            height: 1080,
+            fullscreen: false,
+            vsync: None,
+            subdivide: 1,
+            seed: None,
+            demo: false,
+            attract: None,
+            idle_spin: false,
+            render_to: None,
+            render_steps: 1,
+            frame_budget: None,
+            trails: None,
+            fuse: None,
+            sim_log: None,
+            edge_darkening: None,
+            srgb: false,
+            verbose: false,
+            help: false
+        }
+    }
+}
+
+impl Args {
+    /// Parse the arguments, not including the program name.
+    // The browser build has no command line to parse
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        fn value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+            let value = value.ok_or_else(|| format!("{} needs a value", name))?;
+            value.parse().map_err(|_| format!("Invalid value for {}: {:?}", name, value))
+        }
+
+        let mut result = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => result.width = value(&arg, args.next())?,
+                "--height" => result.height = value(&arg, args.next())?,
+                "--fullscreen" => result.fullscreen = true,
+                "--vsync" => result.vsync = Some(value(&arg, args.next())?),
+                "--subdivide" => result.subdivide = value(&arg, args.next())?,
+                "--seed" => result.seed = Some(value(&arg, args.next())?),
+                "--demo" => result.demo = true,
+                "--attract" => result.attract = Some(value(&arg, args.next())?),
+                "--idle-spin" => result.idle_spin = true,
+                "--render-to" => result.render_to = Some(value(&arg, args.next())?),
+                "--render-steps" => result.render_steps = value(&arg, args.next())?,
+                "--frame-budget" => result.frame_budget = Some(value(&arg, args.next())?),
+                "--trails" => result.trails = Some(value(&arg, args.next())?),
+                "--fuse" => result.fuse = Some(value(&arg, args.next())?),
+                "--edge-darkening" => result.edge_darkening = Some(value(&arg, args.next())?),
+                "--srgb" => result.srgb = true,
+                "--sim-log" => result.sim_log = Some(value(&arg, args.next())?),
+                "--verbose" | "-v" => result.verbose = true,
+                "--help" | "-h" => result.help = true,
+                _ => return Err(format!("Unknown argument {:?}", arg))
+            }
+        }
+
+        if result.width <= 0 || result.height <= 0 {
+            return Err("The window size must be positive".to_string());
+        }
+        if result.subdivide == 0 {
+            return Err("--subdivide must be at least 1".to_string());
+        }
+        if result.frame_budget.is_some_and(|ms| ms.is_nan() || ms <= 0.0) {
+            return Err("--frame-budget must be a positive number of milliseconds".to_string());
+        }
+        if result.trails.is_some_and(|fade| !(0.0..=1.0).contains(&fade)) {
+            return Err("--trails must be from 0 to 1".to_string());
+        }
+        if result.edge_darkening.is_some_and(|amount| !(0.0..=1.0).contains(&amount)) {
+            return Err("--edge-darkening must be from 0 to 1".to_string());
+        }
+        if result.fuse.is_some_and(|seconds| seconds.is_nan() || seconds < 0.0) {
+            return Err("--fuse must be a number of seconds, at least 0".to_string());
+        }
+        if result.attract.is_some_and(|seconds| seconds.is_nan() || seconds < 0.0) {
+            return Err("--attract must be a number of seconds, at least 0".to_string());
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_gives_the_defaults() {
+        let args = parse(&[]).unwrap();
+        assert_eq!((args.width, args.height), (1920, 1080));
+        assert!(!args.fullscreen && !args.demo && !args.srgb && !args.idle_spin);
+        assert_eq!(args.vsync, None);
+        assert_eq!(args.subdivide, 1);
+        assert_eq!(args.seed, None);
+        assert_eq!(args.attract, None);
+        assert_eq!(args.render_to, None);
+        assert_eq!(args.render_steps, 1);
+        assert_eq!(args.frame_budget, None);
+        assert_eq!(args.trails, None);
+        assert_eq!(args.fuse, None);
+        assert_eq!(args.sim_log, None);
+        assert_eq!(args.edge_darkening, None);
+    }
+
+    #[test]
+    fn every_option_is_parsed() {
+        let args = parse(&[
+            "--width", "800", "--height", "600", "--fullscreen", "--vsync", "adaptive",
+            "--subdivide", "3", "--seed", "42", "--demo", "--attract", "30", "--idle-spin", "--render-to", "out.png", "--render-steps", "90", "--frame-budget", "16",
+            "--trails", "0.2", "--fuse", "1.5", "--sim-log", "run.csv", "--edge-darkening", "0.3", "--srgb", "--verbose"
+        ]).unwrap();
+        assert_eq!((args.width, args.height), (800, 600));
+        assert!(args.fullscreen && args.demo && args.verbose && args.srgb && args.idle_spin);
+        assert_eq!(args.vsync, Some(VSyncMode::Adaptive));
+        assert_eq!(args.subdivide, 3);
+        assert_eq!(args.seed, Some(42));
+        assert_eq!(args.attract, Some(30.0));
+        assert_eq!(args.render_to.as_deref(), Some("out.png"));
+        assert_eq!(args.render_steps, 90);
+        assert_eq!(args.frame_budget, Some(16.0));
+        assert_eq!(args.trails, Some(0.2));
+        assert_eq!(args.fuse, Some(1.5));
+        assert_eq!(args.sim_log.as_deref(), Some("run.csv"));
+        assert_eq!(args.edge_darkening, Some(0.3));
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert!(parse(&["--width"]).is_err());
+        assert!(parse(&["--width", "wide"]).is_err());
+        assert!(parse(&["--vsync", "sometimes"]).is_err());
+        assert!(parse(&["--subdivide", "0"]).is_err());
+        assert!(parse(&["--attract", "-1"]).is_err());
+        assert!(parse(&["--frame-budget", "0"]).is_err());
+        assert!(parse(&["--trails", "1.5"]).is_err());
+        assert!(parse(&["--trails", "NaN"]).is_err());
+        assert!(parse(&["--fuse", "-0.5"]).is_err());
+        assert!(parse(&["--edge-darkening", "2"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! A C interface to the simulation, for hosts that do their own rendering.
+//!
+//! The host owns nothing until `cubes_game_state_new` returns, and must hand
+//! the pointer back to `cubes_game_state_free` exactly once when done.
+//! None of these functions touch the window or graphics context.
+
+use crate::game::{GameState, GameInput};
+use crate::game::action::Action;
+use crate::game::cube::SubcubeShape;
+
+/// Mirrors `GameInput` with C-compatible fields.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct CubesInput {
+    pub hurl_all: bool,
+    pub explode_subcube: bool,
+    pub crack_subcube: bool,
+    pub rearrange: bool,
+    pub freeze: bool,
+    pub reset: bool,
+    pub cycle_outline_mode: bool,
+    pub toggle_cull_faces: bool,
+    pub toggle_orbit_inertia: bool,
+    pub toggle_show_ghost: bool,
+    pub cycle_shadow_mode: bool,
+    pub toggle_debug_picking: bool,
+    pub toggle_show_indices: bool,
+    pub cycle_palette_mode: bool,
+    pub cycle_shading_mode: bool,
+    pub cycle_render_style: bool,
+    pub toggle_projection: bool,
+    pub toggle_explode_shape: bool,
+    pub toggle_bounds: bool,
+    pub focus_subcube: bool,
+    pub reset_pivot: bool,
+    pub reset_camera: bool,
+    pub toggle_reticle: bool,
+    pub magnet: bool,
+    pub select_next: bool,
+    pub select_prev: bool,
+    pub explode_selected: bool,
+    pub move_reticle_x: f32,
+    pub move_reticle_y: f32,
+    /// If false, `pointer_x` and `pointer_y` are ignored
+    pub has_pointer: bool,
+    /// From -1.0 to +1.0, where (0.0, 0.0) is the center
+    pub pointer_x: f32,
+    pub pointer_y: f32,
+    pub rotate_view_x: f32,
+    pub rotate_view_y: f32,
+    pub release_view: bool,
+    pub zoom_view_change: f32,
+    pub fov_change: f32
+}
+
+impl From<&CubesInput> for GameInput {
+    fn from(input: &CubesInput) -> GameInput {
+        GameInput {
+            hurl_all: input.hurl_all,
+            explode_subcube: input.explode_subcube,
+            crack_subcube: input.crack_subcube,
+            rearrange: input.rearrange,
+            freeze: input.freeze,
+            reset: input.reset,
+            cycle_outline_mode: input.cycle_outline_mode,
+            toggle_cull_faces: input.toggle_cull_faces,
+            toggle_orbit_inertia: input.toggle_orbit_inertia,
+            toggle_show_ghost: input.toggle_show_ghost,
+            cycle_shadow_mode: input.cycle_shadow_mode,
+            toggle_debug_picking: input.toggle_debug_picking,
+            toggle_show_indices: input.toggle_show_indices,
+            cycle_palette_mode: input.cycle_palette_mode,
+            cycle_shading_mode: input.cycle_shading_mode,
+            cycle_render_style: input.cycle_render_style,
+            toggle_projection: input.toggle_projection,
+            toggle_explode_shape: input.toggle_explode_shape,
+            toggle_bounds: input.toggle_bounds,
+            focus_subcube: input.focus_subcube,
+            reset_pivot: input.reset_pivot,
+            reset_camera: input.reset_camera,
+            toggle_reticle: input.toggle_reticle,
+            magnet: input.magnet,
+            select_next: input.select_next,
+            select_prev: input.select_prev,
+            explode_selected: input.explode_selected,
+            move_reticle: (input.move_reticle_x, input.move_reticle_y),
+            pointer: input.has_pointer.then_some((input.pointer_x, input.pointer_y)),
+            rotate_view: (input.rotate_view_x, input.rotate_view_y),
+            release_view: input.release_view,
+            zoom_view_change: input.zoom_view_change,
+            fov_change: input.fov_change
+        }
+    }
+}
+
+/// How to draw one subcube.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CubesSubcube {
+    /// Column-major model matrix, for a cube ranging from -0.5 to +0.5
+    pub model: [f32; 16],
+    /// Where the subcube came from in the original cube, from 0 to 1 on each axis
+    pub color_coord: [f32; 3],
+    pub subcube_length: f32,
+    pub alpha: f32,
+    /// If false, `color` is unused and the color comes from `color_coord`
+    pub has_color: bool,
+    pub color: [f32; 3],
+    /// If true, draw a sphere fitting inside the subcube instead of the cube
+    pub sphere: bool
+}
+
+fn matrix_to_array(mat: cgmath::Matrix4<f32>) -> [f32; 16] {
+    let cols: [[f32; 4]; 4] = mat.into();
+    let mut arr = [0.0; 16];
+    for (dst, src) in arr.chunks_exact_mut(4).zip(cols) {
+        dst.copy_from_slice(&src);
+    }
+    arr
+}
+
+/// Create a game state. Free it with `cubes_game_state_free`.
+#[no_mangle]
+pub extern "C" fn cubes_game_state_new() -> *mut GameState {
+    Box::into_raw(Box::new(GameState::new()))
+}
+
+/// Free a game state created by `cubes_game_state_new`. Null is ignored.
+///
+/// # Safety
+/// `state` must be null or a pointer from `cubes_game_state_new` that
+/// hasn't been freed yet. It must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cubes_game_state_free(state: *mut GameState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+    }
+}
+
+/// Run one step of the simulation.
+///
+/// If `out_projection_view` isn't null, the column-major projection-view
+/// matrix to draw the step with is written to it.
+/// Returns the index of the subcube under the pointer, or -1 if there's none.
+///
+/// # Safety
+/// `state` must be a live pointer from `cubes_game_state_new`, and `input`
+/// must point to a valid `CubesInput`. `out_projection_view` must be null or
+/// point to 16 writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn cubes_game_state_step(
+    state: *mut GameState,
+    viewport_width: i32,
+    viewport_height: i32,
+    input: *const CubesInput,
+    out_projection_view: *mut f32
+) -> i64 {
+    let state = &mut *state;
+    let input = GameInput::from(&*input);
+
+    let result = state.step((viewport_width, viewport_height), &input);
+
+    if !out_projection_view.is_null() {
+        let arr = matrix_to_array(result.projection_view);
+        std::ptr::copy_nonoverlapping(arr.as_ptr(), out_projection_view, arr.len());
+    }
+
+    match result.selected_subcube {
+        Some(index) => index as i64,
+        None => -1
+    }
+}
+
+/// Do the action named `name` on the next step, such as "hurl_all".
+/// The names are the same as the fields of `CubesInput` that carry them.
+/// Returns false if there's no action by that name.
+///
+/// # Safety
+/// `state` must be a live pointer from `cubes_game_state_new`, and `name`
+/// must be a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cubes_game_state_apply_action(state: *mut GameState, name: *const std::ffi::c_char) -> bool {
+    let name = std::ffi::CStr::from_ptr(name);
+    match name.to_str().ok().and_then(Action::from_name) {
+        Some(action) => {
+            (*state).apply_action(action);
+            true
+        },
+        None => false
+    }
+}
+
+/// Get how many subcubes there are.
+///
+/// # Safety
+/// `state` must be a live pointer from `cubes_game_state_new`.
+#[no_mangle]
+pub unsafe extern "C" fn cubes_game_state_subcube_count(state: *const GameState) -> usize {
+    (*state).cube.subcubes.len()
+}
+
+/// Write up to `capacity` subcubes to `out`, in index order.
+/// Returns how many were written.
+///
+/// # Safety
+/// `state` must be a live pointer from `cubes_game_state_new`, and `out`
+/// must point to at least `capacity` writable `CubesSubcube`s.
+#[no_mangle]
+pub unsafe extern "C" fn cubes_game_state_subcubes(
+    state: *const GameState,
+    out: *mut CubesSubcube,
+    capacity: usize
+) -> usize {
+    let state = &*state;
+
+    let mut written = 0;
+    for subcube in state.cube.iter_render().take(capacity) {
+        let c = subcube.color_coord;
+        out.add(written).write(CubesSubcube {
+            model: matrix_to_array(subcube.model),
+            color_coord: [c.x, c.y, c.z],
+            subcube_length: subcube.subcube_length,
+            alpha: subcube.alpha,
+            has_color: subcube.color.is_some(),
+            color: subcube.color.map_or([0.0; 3], Into::into),
+            sphere: subcube.shape == SubcubeShape::Sphere
+        });
+        written += 1;
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_host_can_step_read_back_and_free() {
+        unsafe {
+            let state = cubes_game_state_new();
+            let explode = CubesInput { explode_subcube: true, has_pointer: true, ..CubesInput::default() };
+            let mut projection_view = [0.0; 16];
+            assert_eq!(cubes_game_state_step(state, 800, 600, &explode, projection_view.as_mut_ptr()), 0);
+            assert!(projection_view.iter().any(|&x| x != 0.0));
+            assert_eq!(cubes_game_state_subcube_count(state), 8);
+
+            // Only as many as there's room for
+            let mut out = [std::mem::MaybeUninit::<CubesSubcube>::uninit(); 3];
+            assert_eq!(cubes_game_state_subcubes(state, out.as_mut_ptr().cast(), out.len()), 3);
+            for subcube in out.iter().map(|s| s.assume_init()) {
+                assert_eq!((subcube.subcube_length, subcube.alpha), (0.5, 1.0));
+                assert!(!subcube.has_color && !subcube.sphere);
+            }
+
+            assert!(cubes_game_state_apply_action(state, c"hurl_all".as_ptr()));
+            assert!(!cubes_game_state_apply_action(state, c"bogus".as_ptr()));
+            assert_eq!(cubes_game_state_step(state, 800, 600, &CubesInput::default(), std::ptr::null_mut()), -1);
+
+            cubes_game_state_free(state);
+            cubes_game_state_free(std::ptr::null_mut());
+        }
+    }
+}
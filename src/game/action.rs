@@ -0,0 +1,96 @@
+//! Everything the demo can be told to do, apart from which key does it.
+
+use super::GameInput;
+
+/// Declares `Action` from its variants and the `GameInput` field carrying
+/// each, so `ALL`, `flag` and `name` can't leave any out.
+macro_rules! actions {
+    ($(#[$meta:meta])* pub enum Action { $($action:ident => $field:ident),* }) => {
+        $(#[$meta])*
+        pub enum Action {
+            $($action),*
+        }
+
+        impl Action {
+            pub const ALL: [Action; [$(Action::$action),*].len()] = [$(Action::$action),*];
+
+            /// The `GameInput` field that carries the action.
+            pub fn flag(self, input: &mut GameInput) -> &mut bool {
+                match self {
+                    $(Action::$action => &mut input.$field),*
+                }
+            }
+
+            /// The name scripts refer to the action by, e.g. "hurl_all".
+            /// The same as the `GameInput` field.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Action::$action => stringify!($field)),*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    /// A one-off input that must be seen by exactly one step.
+    ///
+    /// `HurlAll`, `Freeze`, `Rearrange` and `Reset` only do anything while the
+    /// cube is `Simulating`. `ExplodeSubcube`, `CrackSubcube` and `FocusSubcube`
+    /// act on the subcube under the pointer, so they need `GameInput::pointer` or the
+    /// reticle to be set.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub enum Action {
+        HurlAll => hurl_all,
+        ExplodeSubcube => explode_subcube,
+        CrackSubcube => crack_subcube,
+        Rearrange => rearrange,
+        Freeze => freeze,
+        Reset => reset,
+        CycleOutlineMode => cycle_outline_mode,
+        CycleShadingMode => cycle_shading_mode,
+        CycleRenderStyle => cycle_render_style,
+        ToggleProjection => toggle_projection,
+        CycleShadowMode => cycle_shadow_mode,
+        ToggleExplodeShape => toggle_explode_shape,
+        ToggleBounds => toggle_bounds,
+        ToggleReticle => toggle_reticle,
+        SelectNext => select_next,
+        SelectPrev => select_prev,
+        ExplodeSelected => explode_selected,
+        FocusSubcube => focus_subcube,
+        ResetPivot => reset_pivot,
+        ResetCamera => reset_camera,
+        ToggleCullFaces => toggle_cull_faces,
+        ToggleOrbitInertia => toggle_orbit_inertia,
+        ToggleShowGhost => toggle_show_ghost,
+        ToggleDebugPicking => toggle_debug_picking,
+        ToggleShowIndices => toggle_show_indices,
+        CyclePaletteMode => cycle_palette_mode,
+        ReleaseView => release_view
+    }
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_action_has_its_own_name_and_flag() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(action.name()), Some(action));
+
+            let mut input = GameInput::new();
+            *action.flag(&mut input) = true;
+            let set: Vec<_> = Action::ALL.into_iter().filter(|other| *other.flag(&mut input)).collect();
+            assert_eq!(set, [action]);
+        }
+        assert_eq!(Action::from_name("bogus"), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Unattended play for kiosks, once nobody has touched the demo for a while.
+
+use super::{GameInput, GameState};
+use super::cube::CubeStateKind;
+
+/// How fast the view orbits, in the same units as `GameInput::rotate_view`
+const ORBIT_SPEED: f32 = 0.005;
+/// Seconds between explosions
+const EXPLODE_INTERVAL: f32 = 2.0;
+/// Explosions before letting things settle
+const EXPLOSIONS_PER_ROUND: u32 = 4;
+/// Seconds to wait for the pieces to settle, at least and at most
+const SETTLE_TIME: (f32, f32) = (2.0, 8.0);
+/// Rounds ending with a rearrange before one ends with a reset instead
+const ROUNDS_BEFORE_RESET: u32 = 3;
+
+fn seconds_to_steps(seconds: f32) -> u32 {
+    (seconds * GameState::steps_per_second() as f32).round() as u32
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Phase {
+    Exploding { explosions: u32 },
+    Settling,
+    Rearranging
+}
+
+/// Orbits the view, explodes random subcubes, waits for the pieces to
+/// settle, then puts the cube back together and goes again.
+///
+/// It only ever produces `GameInput`, so it plays the same way a person
+/// would. Any real input stops it until there's been none for `idle_steps`.
+pub struct AttractMode {
+    idle_steps: u32,
+    idle_for: u32,
+    phase: Phase,
+    phase_steps: u32,
+    rounds: u32
+}
+
+impl AttractMode {
+    pub fn new(idle_seconds: f32) -> AttractMode {
+        AttractMode {
+            idle_steps: seconds_to_steps(idle_seconds),
+            idle_for: 0,
+            phase: Phase::Exploding { explosions: 0 },
+            phase_steps: 0,
+            rounds: 0
+        }
+    }
+
+    /// Leave attract mode, and start waiting for no input again.
+    pub fn interrupt(&mut self) {
+        self.idle_for = 0;
+        self.phase = Phase::Exploding { explosions: 0 };
+        self.phase_steps = 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.idle_for >= self.idle_steps
+    }
+
+    /// Get the input for the next step of `state`, or None while waiting for idleness.
+    ///
+    /// Which subcube explodes is chosen with `quad_rand`, so a seeded run plays out the same.
+    pub fn next_input(&mut self, state: &GameState, viewport: (i32, i32)) -> Option<GameInput> {
+        if !self.is_active() {
+            self.idle_for += 1;
+            return None;
+        }
+
+        let mut input = GameInput { rotate_view: (ORBIT_SPEED, 0.0), ..GameInput::new() };
+        self.phase_steps += 1;
+
+        match self.phase {
+            Phase::Exploding { explosions } => {
+                if self.phase_steps >= seconds_to_steps(EXPLODE_INTERVAL) {
+                    input.pointer = random_subcube_pointer(state, viewport);
+                    input.explode_subcube = input.pointer.is_some();
+
+                    self.phase_steps = 0;
+                    self.phase = match explosions + 1 {
+                        n if n >= EXPLOSIONS_PER_ROUND => Phase::Settling,
+                        n => Phase::Exploding { explosions: n }
+                    };
+                }
+            },
+            Phase::Settling => {
+                let (min, max) = SETTLE_TIME;
+                let settled = state.cube.is_settled();
+                if self.phase_steps >= seconds_to_steps(max) || (self.phase_steps >= seconds_to_steps(min) && settled) {
+                    self.rounds += 1;
+                    if self.rounds.is_multiple_of(ROUNDS_BEFORE_RESET) {
+                        input.reset = true;
+                    } else {
+                        input.rearrange = true;
+                    }
+
+                    self.phase_steps = 0;
+                    self.phase = Phase::Rearranging;
+                }
+            },
+            Phase::Rearranging => {
+                // Resets also pass through Resetting on their way back to Simulating
+                if state.cube.state_kind() == CubeStateKind::Simulating {
+                    self.phase_steps = 0;
+                    self.phase = Phase::Exploding { explosions: 0 };
+                }
+            }
+        }
+
+        Some(input)
+    }
+}
+
+/// Where on screen a random subcube is, if it's in front of the camera.
+fn random_subcube_pointer(state: &GameState, viewport: (i32, i32)) -> Option<(f32, f32)> {
+    let subcubes = &state.cube.subcubes;
+    if subcubes.is_empty() {
+        return None;
+    }
+    let index = quad_rand::gen_range(0, subcubes.len());
+
+    let clip = state.solve_projection_view(viewport) * subcubes[index].pos.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+    (x.abs() <= 1.0 && y.abs() <= 1.0).then_some((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: (i32, i32) = (640, 480);
+
+    /// Step `state` with attract mode's input, or no input while it waits.
+    fn run(attract: &mut AttractMode, state: &mut GameState, steps: u32) -> Vec<GameInput> {
+        (0..steps).map(|_| {
+            let input = attract.next_input(state, VIEWPORT).unwrap_or_default();
+            state.step(VIEWPORT, &input);
+            input
+        }).collect()
+    }
+
+    #[test]
+    fn starts_after_the_idle_time_and_stops_on_interrupt() {
+        let state = GameState::new();
+        let mut attract = AttractMode::new(1.0);
+
+        for _ in 0..GameState::steps_per_second() {
+            assert!(attract.next_input(&state, VIEWPORT).is_none());
+        }
+        assert!(attract.next_input(&state, VIEWPORT).is_some());
+
+        attract.interrupt();
+        assert!(!attract.is_active());
+        assert!(attract.next_input(&state, VIEWPORT).is_none());
+    }
+
+    #[test]
+    fn explodes_then_puts_the_cube_back() {
+        quad_rand::srand(1);
+        let mut state = GameState::new();
+        let mut attract = AttractMode::new(0.0);
+
+        let explode_steps = seconds_to_steps(EXPLODE_INTERVAL) * EXPLOSIONS_PER_ROUND;
+        let inputs = run(&mut attract, &mut state, explode_steps);
+        assert!(inputs.iter().any(|input| input.explode_subcube));
+        assert!(state.cube.subcubes.len() > 1);
+
+        let (_, max_settle) = SETTLE_TIME;
+        let inputs = run(&mut attract, &mut state, seconds_to_steps(max_settle));
+        assert!(inputs.iter().any(|input| input.rearrange));
+    }
+}
@@ -1,9 +1,40 @@
 use std;
 
 use cgmath::prelude::*;
-use cgmath::{Vector3, Quaternion};
+use cgmath::{Vector3, Quaternion, Point3};
 use collision::{Ray3};
 
+use super::palette::{Palette, PaletteMode};
+
+/// How much of the way to the original arrangement subcubes move every step
+/// while rearranging.
+// TODO - figure out the math on this (using frac)
+const REARRANGE_LERP_AMOUNT: f32 = 0.1;
+
+/// `Subcube::outer_faces` for a subcube with every face on the outside
+const ALL_FACES: u8 = 0b11_1111;
+
+/// Below this speed, a subcube counts as settled.
+pub const REST_SPEED: f32 = 0.1;
+
+// The wobble a subcube can make on settling: how long it lasts in seconds,
+// how fast it rocks in hertz, and how far it first tips in radians
+const WOBBLE_DURATION: f32 = 0.5;
+const WOBBLE_FREQUENCY: f32 = 6.0;
+const WOBBLE_ANGLE: f32 = 0.15;
+
+// The squash a subcube can make on hitting a wall: how long it takes to
+// spring back in seconds, the most it flattens by, and the speed into the
+// wall that flattens it that much
+const SQUASH_DURATION: f32 = 0.25;
+const SQUASH_AMOUNT: f32 = 0.35;
+const SQUASH_SPEED: f32 = 4.0;
+
+/// Subcubes turned less than this, as the squared length of their
+/// orientation's vector part, are picked as if they hadn't turned at all.
+/// About a thousandth of a degree.
+const UNROTATED_EPSILON: f32 = 1e-10;
+
 struct CubeStateRearranging {
     p: f32,
     next_state: Box<CubeState>
@@ -15,29 +46,318 @@ enum CubeState {
     Rearranging(CubeStateRearranging)
 }
 
+/// What the cube is doing, without the details of each state.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CubeStateKind {
+    Simulating,
+    Resetting,
+    Rearranging
+}
+
+/// The mesh a subcube is drawn with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubcubeShape {
+    Cube,
+    /// A sphere fitting inside the subcube.
+    /// It's still picked and split as if it were the whole subcube.
+    Sphere
+}
+
+impl SubcubeShape {
+    pub fn next(self) -> SubcubeShape {
+        match self {
+            SubcubeShape::Cube => SubcubeShape::Sphere,
+            SubcubeShape::Sphere => SubcubeShape::Cube
+        }
+    }
+}
+
+/// How the outward push of a hurl depends on the distance from its origin.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FalloffKind {
+    /// Grows with distance, so the blast keeps its shape as it expands
+    Linear,
+    /// Violent up close, and barely anything far away
+    InverseSquare,
+    /// The same speed outward at any distance
+    Constant
+}
+
+impl FalloffKind {
+    /// The distance at which every kind pushes equally hard
+    const MATCHING_DISTANCE: f32 = 0.5;
+    /// Inverse-square pushes are capped as if from at least this far away
+    const MIN_DISTANCE: f32 = 0.05;
+
+    /// The outward push at offset `diff` from the origin.
+    fn push(self, diff: Vector3<f32>) -> Vector3<f32> {
+        const LINEAR_SCALE: f32 = 16.0;
+
+        let distance = diff.magnitude();
+        if distance == 0.0 {
+            return Zero::zero();
+        }
+        let direction = diff / distance;
+        let matching = LINEAR_SCALE * FalloffKind::MATCHING_DISTANCE;
+
+        match self {
+            FalloffKind::Linear => diff * LINEAR_SCALE,
+            FalloffKind::InverseSquare => {
+                let d = distance.max(FalloffKind::MIN_DISTANCE) / FalloffKind::MATCHING_DISTANCE;
+                direction * (matching / (d * d))
+            },
+            FalloffKind::Constant => direction * matching
+        }
+    }
+}
+
 pub struct Cube {
     pub subcubes: Vec<Subcube>,
+    /// If true, explosions are weaker for smaller subcubes.
+    /// The force is scaled by the square root of the subcube's length.
+    pub force_scales_with_size: bool,
+    /// If true, hurls and shockwaves are impulses divided by each subcube's
+    /// mass, so bigger pieces move less. Small pieces get very fast, so
+    /// this is best paired with `max_speed`.
+    /// The original cube has a mass of 1, so it moves the same either way.
+    pub motion_scales_with_mass: bool,
+    /// The fastest a subcube can travel, in units per second.
+    /// Unbounded by default.
+    pub max_speed: f32,
+    /// The longest a subcube's angular momentum can be. Like the angular
+    /// momentum it's in half-radians per second, so subcubes spin at up to
+    /// twice this. See `Subcube::angular_momentum`. Unbounded by default.
+    pub max_angular_speed: f32,
+    /// Seconds spent moving subcubes back to their original arrangement
+    /// before they snap into place.
+    pub rearrange_duration: f32,
+    /// How far an explosion shoves the surrounding subcubes.
+    pub shockwave_radius: f32,
+    /// How hard an explosion shoves the surrounding subcubes.
+    /// The shove weakens linearly to nothing at `shockwave_radius`.
+    /// 0 disables the shockwave.
+    pub shockwave_force: f32,
+    /// A point to pull subcubes toward every step, and how much speed the
+    /// pull adds per second. The pull weakens linearly to nothing at
+    /// `magnet_radius`. None by default.
+    pub magnet: Option<(Vector3<f32>, f32)>,
+    pub magnet_radius: f32,
+    /// Added to the outward push of every hurl and explosion, before the
+    /// random variance. e.g. `Vector3::unit_y() * 8.0` sends everything
+    /// upward. Zero by default, for a burst in every direction.
+    pub hurl_bias: Vector3<f32>,
+    /// How hurls and explosions weaken with distance from where they start.
+    pub hurl_falloff: FalloffKind,
+    /// A box, from its lowest to highest corner, that subcubes bounce off
+    /// the inside of. None lets them fly off forever.
+    pub bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+    /// How much speed a subcube keeps when bouncing off `bounds`, from 0 to 1.
+    pub restitution: f32,
+    /// Rock subcubes briefly as they come to rest, so they don't stop dead.
+    /// It's only for show: picking and `is_settled` ignore it.
+    pub settle_wobble: bool,
+    /// Which way is up, as a unit vector. Settling subcubes tip over about it.
+    /// `GameState::set_up_axis` keeps it in line with the rest of the scene.
+    pub up_axis: Vector3<f32>,
+    /// Squash subcubes against the walls of `bounds` when they hit them, and
+    /// stretch them the other ways, springing back soon after. Also only for show.
+    pub squash_on_impact: bool,
+    /// Let rays pick every subcube, ignoring `Subcube::pickable`. For debugging.
+    pub pick_unpickable: bool,
+    /// The shape of the pieces an exploded subcube splits into.
+    pub explode_shape: SubcubeShape,
+    /// Subcubes shorter than this are hurled instead of split when exploded.
+    min_subcube_length: f32,
+    /// The subcubes the cube was created with.
+    /// Resetting returns to this arrangement.
+    initial_subcubes: Vec<Subcube>,
+    /// Explosions waiting on their fuses, in the order they were armed
+    armed: Vec<ArmedExplosion>,
+    /// Bumped whenever the subcubes might have changed. See `version`.
+    version: u64,
+    /// Bumped whenever the subcubes are replaced. See `generation`.
+    generation: u64,
     state: CubeState
 }
 
+/// An explosion set to go off once `remaining` seconds have passed.
+#[derive(Copy, Clone)]
+struct ArmedExplosion {
+    index: usize,
+    remaining: f32,
+    force: f32,
+    subdivide_count: u32,
+    min_subcube_length: f32
+}
+
+/// Returned by `Cube::stats`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CubeStats {
+    pub subcube_count: usize,
+    /// The sum of every subcube's `Subcube::kinetic_energy`
+    pub kinetic_energy: f32,
+    /// The mean of the subcubes' centers. Every subcube counts the same,
+    /// whatever its size.
+    pub centroid: Vector3<f32>,
+    /// The size of the smallest box around every subcube, ignoring how
+    /// they're rotated
+    pub size: Vector3<f32>
+}
+
+/// An armed explosion that went off during a step.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Detonation {
+    pub index: usize,
+    /// Where the subcube was when it went off
+    pub pos: Vector3<f32>,
+    pub subcube_length: f32,
+    /// False if the subcube was too short to split, and was only hurled
+    pub split: bool
+}
+
+/// Everything a renderer needs to draw a subcube.
 #[derive(Copy, Clone)]
+pub struct SubcubeRender {
+    pub index: usize,
+    pub model: cgmath::Matrix4<f32>,
+    /// The center of the subcube, in world space
+    pub pos: Vector3<f32>,
+    pub subcube_length: f32,
+    /// The lowest corner of the subcube's original place in the cube,
+    /// ranging from 0 to 1 on each axis. The color is derived from this.
+    pub color_coord: Vector3<f32>,
+    pub speed: f32,
+    pub alpha: f32,
+    /// Drawn instead of the color from `color_coord`, if set
+    pub color: Option<Vector3<f32>>,
+    /// Which faces were on the outside of the original cube, as in `Subcube::outer_faces`
+    pub outer_faces: u8,
+    pub shape: SubcubeShape,
+    /// Whether hovering, selecting or arming subcube `index` shows on this.
+    /// Renderers clear it on copies drawn for effect, like a ghost outline.
+    pub highlightable: bool
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Subcube {
     pub segment: Vector3<f32>,
     pub subcube_length: f32,
     pub pos: Vector3<f32>,
     pub orientation: Quaternion<f32>,
+    /// Opacity, from 0 (invisible) to 1 (opaque).
+    /// Subdivided subcubes inherit it from their parent.
+    pub alpha: f32,
+    /// How much longer or shorter than `subcube_length` it is along each
+    /// world axis, from (1, 1, 1). Subdivided subcubes inherit it.
+    pub scale: Vector3<f32>,
+    /// Whether rays can pick the subcube. Unpickable ones, such as faint
+    /// overlays, let clicks through to what's behind them.
+    /// Subdivided subcubes inherit it from their parent.
+    pub pickable: bool,
+    pub shape: SubcubeShape,
 
     vel: Vector3<f32>,
-    angular_momentum: Vector3<f32>
+    angular_momentum: Vector3<f32>,
+    color: Option<Vector3<f32>>,
+    outer_faces: u8,
+    #[cfg_attr(feature = "serde", serde(default))]
+    depth: u32,
+    /// The axis it's rocking about and the seconds since it settled, while it wobbles
+    wobble: Option<(Vector3<f32>, f32)>,
+    /// The axis it hit a wall along, how hard, and the seconds since, while it springs back
+    squash: Option<(usize, f32, f32)>
 }
 
 impl Cube {
     pub fn new() -> Cube {
-        let subcubes = vec![Subcube::from_segment(Zero::zero(), 1.0)];
+        Cube::new_subdivided(1)
+    }
+
+    /// Bounds framing the cube with some room to fly around in.
+    pub fn default_bounds() -> (Vector3<f32>, Vector3<f32>) {
+        (Vector3::from_value(-2.0), Vector3::from_value(2.0))
+    }
 
-        Cube {
-            subcubes: subcubes,
+    /// Create the cube already split `n` ways on each axis, e.g. 3 for a
+    /// 3x3x3 arrangement. The subcubes start at rest.
+    ///
+    /// # Panics
+    /// Panics if `n` is 0.
+    pub fn new_subdivided(n: u32) -> Cube {
+        assert!(n > 0);
+
+        let mut cube = Cube {
+            subcubes: vec![Subcube::from_segment(Zero::zero(), 1.0)],
+            force_scales_with_size: false,
+            motion_scales_with_mass: false,
+            max_speed: f32::INFINITY,
+            max_angular_speed: f32::INFINITY,
+            rearrange_duration: 1.5,
+            shockwave_radius: 0.5,
+            shockwave_force: 0.0,
+            magnet: None,
+            magnet_radius: 1.5,
+            hurl_bias: Zero::zero(),
+            hurl_falloff: FalloffKind::Linear,
+            bounds: None,
+            restitution: 0.6,
+            settle_wobble: false,
+            up_axis: Vector3::unit_y(),
+            squash_on_impact: false,
+            pick_unpickable: false,
+            explode_shape: SubcubeShape::Cube,
+            min_subcube_length: 1.0/16.0,
+            initial_subcubes: Vec::new(),
+            armed: Vec::new(),
+            version: 0,
+            generation: 0,
             state: CubeState::Simulating
+        };
+        if n > 1 {
+            // Split without hurling, so the pieces stay at rest
+            cube.subdivide_subcube(0, n);
+        }
+        cube.initial_subcubes = cube.subcubes.clone();
+        cube
+    }
+
+    /// A count that changes whenever a method of the cube might have changed
+    /// the subcubes, so anything worked out from them can be kept until then.
+    /// It stays the same over steps where nothing moves.
+    ///
+    /// Changes made straight to `subcubes` aren't seen. Follow them with
+    /// `mark_changed`.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Bump `version`, after changing `subcubes` directly.
+    pub fn mark_changed(&mut self) {
+        self.version = self.version.wrapping_add(1);
+    }
+
+    /// A count that changes whenever the subcubes are replaced or removed,
+    /// such as when a reset finishes, so an index held from before may now
+    /// name a different subcube. Splitting a subcube doesn't change it, since
+    /// every subcube keeps its index.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Bump `generation` and `version` together.
+    fn replace_subcubes(&mut self, subcubes: Vec<Subcube>) {
+        self.subcubes = subcubes;
+        self.generation = self.generation.wrapping_add(1);
+        self.mark_changed();
+    }
+
+    pub fn state_kind(&self) -> CubeStateKind {
+        match self.state {
+            CubeState::Simulating => CubeStateKind::Simulating,
+            CubeState::Resetting => CubeStateKind::Resetting,
+            CubeState::Rearranging(_) => CubeStateKind::Rearranging
         }
     }
 
@@ -59,9 +379,35 @@ impl Cube {
     pub fn try_hurl_all(&mut self, force: f32) {
         self.try_on_simulating(|_self| {
             let origin = Vector3::from_value(0.0);
+            let (scale_with_mass, bias, falloff) = (_self.motion_scales_with_mass, _self.hurl_bias, _self.hurl_falloff);
             for subcube in _self.subcubes.iter_mut() {
-                subcube.hurl(force, &origin);
+                subcube.hurl(subcube.force_for_mass(force, scale_with_mass), &origin, &bias, falloff);
             }
+            _self.mark_changed();
+        });
+    }
+
+    /// The subcubes that resetting returns to.
+    pub fn initial_subcubes(&self) -> &[Subcube] {
+        &self.initial_subcubes
+    }
+
+    /// Replace the subcubes, and the ones resetting returns to.
+    /// Any rearrange or reset in progress is dropped.
+    pub fn restore(&mut self, subcubes: Vec<Subcube>, initial_subcubes: Vec<Subcube>) {
+        self.replace_subcubes(subcubes);
+        self.initial_subcubes = initial_subcubes;
+        self.armed.clear();
+        self.state = CubeState::Simulating;
+    }
+
+    /// Stop every subcube where it is, leaving the debris in place.
+    pub fn try_freeze(&mut self) {
+        self.try_on_simulating(|_self| {
+            for subcube in _self.subcubes.iter_mut() {
+                subcube.cancel_momentum();
+            }
+            _self.mark_changed();
         });
     }
 
@@ -70,6 +416,8 @@ impl Cube {
             for subcube in _self.subcubes.iter_mut() {
                 subcube.cancel_momentum();
             }
+            _self.armed.clear();
+            _self.mark_changed();
             _self.state = CubeState::Rearranging(CubeStateRearranging{
                 p: 0.0,
                 next_state: Box::new(CubeState::Simulating)
@@ -82,6 +430,8 @@ impl Cube {
             for subcube in _self.subcubes.iter_mut() {
                 subcube.cancel_momentum();
             }
+            _self.armed.clear();
+            _self.mark_changed();
             _self.state = CubeState::Rearranging(CubeStateRearranging{
                 p: 0.0,
                 next_state: Box::new(CubeState::Resetting)
@@ -92,6 +442,7 @@ impl Cube {
     fn subdivide_subcube(&mut self, index: usize, subdivide_count: u32) -> Vec<usize> {
         assert!(subdivide_count > 0);
         let original = self.subcubes[index];
+        self.mark_changed();
 
         // Alter the subcube at the specified index
         self.subcubes[index] = original.get_subdivided_subcube(subdivide_count, (0,0,0));
@@ -117,50 +468,181 @@ impl Cube {
         result
     }
 
-    pub fn explode_subcube(&mut self, index: usize, force: f32, subdivide_count: u32) {
-        let origin = self.subcubes[index].pos;
-
-        let subcubes_idx = self.subdivide_subcube(index, subdivide_count);
-        for &subcube_idx in subcubes_idx.iter() {
-            let subcube = &mut self.subcubes[subcube_idx];
-            subcube.hurl(force, &origin);
+    /// The force to explode the subcube at the specified index with.
+    fn explode_force(&self, index: usize, force: f32) -> f32 {
+        if self.force_scales_with_size {
+            force * self.subcubes[index].subcube_length.sqrt()
+        } else {
+            force
         }
     }
 
+    pub fn min_subcube_length(&self) -> f32 {
+        self.min_subcube_length
+    }
+
+    /// Set how small subcubes can get from explosions.
+    /// Zero lets them split forever.
+    ///
+    /// # Panics
+    /// Panics if `length` is negative or NaN.
+    pub fn set_min_subcube_length(&mut self, length: f32) {
+        assert!(length >= 0.0);
+        self.min_subcube_length = length;
+    }
+
+    /// Split the subcube and hurl its pieces, unless it's already shorter
+    /// than `min_subcube_length()`.
+    pub fn explode_subcube(&mut self, index: usize, force: f32, subdivide_count: u32) {
+        self.explode_subcube_if_at_least(index, force, subdivide_count, self.min_subcube_length);
+    }
+
+    /// Like `explode_subcube`, but with a one-off minimum length.
     pub fn explode_subcube_if_at_least(&mut self, index: usize, force: f32, subdivide_count: u32, min_subcube_length: f32) {
         if self.subcubes[index].subcube_length >= min_subcube_length {
-            self.explode_subcube(index, force, subdivide_count);
+            self.split_and_hurl(index, force, subdivide_count);
         } else {
             // Still hurl the subcube
+            let force = self.explode_force(index, force);
+            let (scale_with_mass, bias, falloff) = (self.motion_scales_with_mass, self.hurl_bias, self.hurl_falloff);
             let s = &mut self.subcubes[index];
             let origin = s.pos;
-            s.hurl(force, &origin);
+            s.hurl(s.force_for_mass(force, scale_with_mass), &origin, &bias, falloff);
+            self.mark_changed();
+        }
+    }
+
+    /// Explode the subcube like `explode_subcube_if_at_least` once `delay`
+    /// seconds of simulation have passed. Any number can be armed at once,
+    /// even several on the same subcube. Rearranging or resetting disarms
+    /// them all, and nothing is armed outside the Simulating state.
+    pub fn arm_explosion(&mut self, index: usize, delay: f32, force: f32, subdivide_count: u32, min_subcube_length: f32) {
+        assert!(index < self.subcubes.len());
+        if self.state_kind() != CubeStateKind::Simulating {
+            return;
+        }
+        self.armed.push(ArmedExplosion {
+            index,
+            remaining: delay,
+            force,
+            subdivide_count,
+            min_subcube_length
+        });
+    }
+
+    /// Seconds until the soonest armed explosion on the subcube goes off,
+    /// or None if it has none.
+    pub fn fuse_remaining(&self, index: usize) -> Option<f32> {
+        self.armed.iter()
+            .filter(|armed| armed.index == index)
+            .map(|armed| armed.remaining)
+            .reduce(f32::min)
+    }
+
+    /// Every armed explosion's subcube index and seconds left, in the order
+    /// they were armed. A subcube can appear more than once.
+    pub fn fuses(&self) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.armed.iter().map(|armed| (armed.index, armed.remaining))
+    }
+
+    /// Count down the armed explosions and set off the ones that are due.
+    fn step_fuses(&mut self, frac: f32) -> Vec<Detonation> {
+        for armed in self.armed.iter_mut() {
+            armed.remaining -= frac;
+        }
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.armed).into_iter()
+            .partition(|armed| armed.remaining <= 0.0);
+        self.armed = waiting;
+
+        // Shards are pushed at the end, so the indices of the others stay put
+        due.into_iter().map(|armed| {
+            let Subcube { pos, subcube_length, .. } = self.subcubes[armed.index];
+            let count = self.subcubes.len();
+            self.explode_subcube_if_at_least(armed.index, armed.force, armed.subdivide_count, armed.min_subcube_length);
+            Detonation { index: armed.index, pos, subcube_length, split: self.subcubes.len() != count }
+        }).collect()
+    }
+
+    /// Split the subcube into its grid of shards and leave them at rest where
+    /// they are, tiling its old place, unless it's already shorter than
+    /// `min_subcube_length`. Returns true if it split.
+    pub fn crack_subcube(&mut self, index: usize, subdivide_count: u32, min_subcube_length: f32) -> bool {
+        if self.subcubes[index].subcube_length < min_subcube_length {
+            return false;
+        }
+        for subcube_idx in self.subdivide_subcube(index, subdivide_count) {
+            self.subcubes[subcube_idx].cancel_momentum();
         }
+        true
     }
 
-    /// Integrate the cube simulation by stepping all subcubes
-    pub fn step(&mut self, frac: f32) {
+    fn split_and_hurl(&mut self, index: usize, force: f32, subdivide_count: u32) {
+        let origin = self.subcubes[index].pos;
+        let force = self.explode_force(index, force);
+
+        let (scale_with_mass, bias, falloff) = (self.motion_scales_with_mass, self.hurl_bias, self.hurl_falloff);
+
+        let subcubes_idx = self.subdivide_subcube(index, subdivide_count);
+        for &subcube_idx in subcubes_idx.iter() {
+            let subcube = &mut self.subcubes[subcube_idx];
+            subcube.shape = self.explode_shape;
+            subcube.hurl(subcube.force_for_mass(force, scale_with_mass), &origin, &bias, falloff);
+        }
+
+        if self.shockwave_force > 0.0 {
+            let (radius, force) = (self.shockwave_radius, self.shockwave_force);
+            for (i, subcube) in self.subcubes.iter_mut().enumerate() {
+                if !subcubes_idx.contains(&i) {
+                    subcube.shove(subcube.force_for_mass(force, scale_with_mass), radius, &origin);
+                }
+            }
+        }
+    }
+
+    /// Integrate the cube simulation by stepping all subcubes.
+    /// Returns the armed explosions that went off.
+    pub fn step(&mut self, frac: f32) -> Vec<Detonation> {
+        let mut detonations = Vec::new();
         let next_state = match self.state {
             CubeState::Simulating => {
+                detonations = self.step_fuses(frac);
+                // Still subcubes stay where they are, so skip the bump. Wobbles and
+                // squashes are only for show and don't count.
+                if self.magnet.is_some() || !self.subcubes.iter().all(Subcube::is_still) {
+                    self.mark_changed();
+                }
+                if let Some((point, strength)) = self.magnet {
+                    let scale_with_mass = self.motion_scales_with_mass;
+                    for subcube in self.subcubes.iter_mut() {
+                        // A shove toward the point rather than away from it
+                        let force = subcube.force_for_mass(strength * frac, scale_with_mass);
+                        subcube.shove(-force, self.magnet_radius, &point);
+                    }
+                }
                 for subcube in self.subcubes.iter_mut() {
-                    subcube.step(frac);
+                    subcube.step(frac, self.max_speed, self.max_angular_speed, self.settle_wobble.then_some(self.up_axis));
+                    if let Some((min, max)) = self.bounds {
+                        subcube.bounce_within(min, max, self.restitution, self.squash_on_impact);
+                    }
                 }
                 None
             },
             CubeState::Resetting => {
-                self.subcubes = vec![Subcube::from_segment(Zero::zero(), 1.0)];
+                self.replace_subcubes(self.initial_subcubes.clone());
 
                 Some(CubeState::Simulating)
             },
             CubeState::Rearranging(ref mut s) => {
+                // `s` borrows the state, so bump the version without `mark_changed`
+                self.version = self.version.wrapping_add(1);
                 for subcube in self.subcubes.iter_mut() {
-                    subcube.approach_original_arrangement(frac);
+                    subcube.approach_original_arrangement(REARRANGE_LERP_AMOUNT);
                 }
 
-                // Go to the next state after 1.5 seconds
+                // Go to the next state after the rearrange duration
                 s.p += frac;
                 match s.p {
-                    0.0..=1.5 => None,
+                    p if p <= self.rearrange_duration => None,
                     _ => {
                         for subcube in self.subcubes.iter_mut() {
                             subcube.reset();
@@ -178,116 +660,375 @@ impl Cube {
             }
         };
 
-        match next_state {
-            Some(s) => self.state = s,
-            None => ()
-        };
+        if let Some(s) = next_state {
+            self.state = s;
+        }
+        detonations
+    }
+
+    /// The model matrix of each subcube at its original place in the cube,
+    /// in the same order as `subcubes`.
+    pub fn iter_rest_poses(&self) -> impl Iterator<Item = cgmath::Matrix4<f32>> + '_ {
+        use crate::util::matrix::MatrixBuilder;
+        self.subcubes.iter().map(|subcube| {
+            cgmath::Matrix4::identity()
+                .translate_v(&subcube.segment)
+                .scale_s(subcube.subcube_length)
+        })
+    }
+
+    /// Whether every subcube has come to rest.
+    pub fn is_settled(&self) -> bool {
+        self.subcubes.iter().all(Subcube::is_settled)
+    }
+
+    /// Totals and extents over every subcube, as of the last step.
+    pub fn stats(&self) -> CubeStats {
+        let count = self.subcubes.len();
+        let (mut min, mut max) = (Vector3::from_value(f32::INFINITY), Vector3::from_value(f32::NEG_INFINITY));
+        let mut sum = Vector3::zero();
+        let mut kinetic_energy = 0.0;
+        for subcube in self.subcubes.iter() {
+            let half = subcube.scale * (subcube.subcube_length / 2.0);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(subcube.pos[axis] - half[axis]);
+                max[axis] = max[axis].max(subcube.pos[axis] + half[axis]);
+            }
+            sum += subcube.pos;
+            kinetic_energy += subcube.kinetic_energy();
+        }
+
+        match count {
+            0 => CubeStats { subcube_count: 0, kinetic_energy: 0.0, centroid: Zero::zero(), size: Zero::zero() },
+            _ => CubeStats { subcube_count: count, kinetic_energy, centroid: sum / count as f32, size: max - min }
+        }
+    }
+
+    /// Color every subcube from `palette`, replacing any colors they had.
+    pub fn apply_palette(&mut self, palette: &Palette, mode: PaletteMode) {
+        for subcube in &mut self.subcubes {
+            subcube.apply_palette(palette, mode);
+        }
+        self.mark_changed();
+    }
+
+    /// Remove the subcubes `f` returns false for, keeping the rest in order.
+    /// Returns where each subcube went, by its old index: its new index, or
+    /// None if it was removed.
+    ///
+    /// Indices held from before are stale afterwards, and can be mapped
+    /// with the result. `generation` changes, so `GameState`'s selection is
+    /// cleared; use `GameState::retain_subcubes` to keep it instead. Armed
+    /// explosions are moved along with their subcubes, and dropped with
+    /// removed ones. What resetting returns to is kept.
+    pub fn retain_subcubes<F: FnMut(&Subcube) -> bool>(&mut self, mut f: F) -> Vec<Option<usize>> {
+        let mut kept = 0;
+        let map: Vec<Option<usize>> = self.subcubes.iter().map(|subcube| {
+            if f(subcube) {
+                kept += 1;
+                Some(kept - 1)
+            } else {
+                None
+            }
+        }).collect();
+
+        let mut keep = map.iter().map(Option::is_some);
+        self.subcubes.retain(|_| keep.next().unwrap());
+        self.armed.retain_mut(|armed| match map[armed.index] {
+            Some(new_index) => {
+                armed.index = new_index;
+                true
+            },
+            None => false
+        });
+        self.generation = self.generation.wrapping_add(1);
+        self.mark_changed();
+        map
+    }
+
+    /// Remove the subcubes whose centers are lower than `height` along
+    /// `up_axis`, such as debris that's fallen through the floor.
+    /// Returns the same as `retain_subcubes`.
+    pub fn cull_below(&mut self, height: f32) -> Vec<Option<usize>> {
+        let up = self.up_axis;
+        self.retain_subcubes(|subcube| subcube.pos.dot(up) >= height)
+    }
+
+    /// Visit every subcube mutably, e.g. to recolor or nudge them.
+    pub fn for_each_subcube_mut<F: FnMut(&mut Subcube)>(&mut self, f: F) {
+        self.subcubes.iter_mut().for_each(f);
+        self.mark_changed();
+    }
+
+    pub fn iter_render(&self) -> impl Iterator<Item = SubcubeRender> + '_ {
+        self.subcubes.iter().enumerate().map(|(index, subcube)| SubcubeRender {
+            index,
+            model: subcube.model_matrix_at(&subcube.display_orientation(), &subcube.display_scale()),
+            pos: subcube.pos,
+            subcube_length: subcube.subcube_length,
+            color_coord: subcube.segment + Vector3::from_value(0.5 - subcube.subcube_length / 2.0),
+            speed: subcube.vel.magnitude(),
+            alpha: subcube.alpha,
+            color: subcube.color,
+            outer_faces: subcube.outer_faces,
+            shape: subcube.shape,
+            highlightable: true
+        })
     }
 
     /// Get the closest subcube that intersects with the ray.
     /// Returns a Some tuple with the index and a reference to the subcube
     /// if one intersects with the ray.
     /// Returns None if no subcube intersects with the ray.
+    /// If several are equally close, the lowest index wins.
     pub fn get_subcube_from_ray(&self, ray: &Ray3<f32>) -> Option<(usize, &Subcube)> {
-        use collision::Ray;
-        use crate::util::compare::CompareSmallest;
-        use std::cmp::Ordering;
-
-        fn intersects_with_unit_cube(ray: &Ray3<f32>) -> Option<f32> {
-            use cgmath::Point3;
-            use collision::{Continuous, Plane};
-            // The unit cube is at the origin, from -0.5..+0.5
-
-            static PLANES: [Plane<f32>; 6] = [
-                Plane { n: Vector3::new( 1.0,  0.0,  0.0), d: 0.5 },
-                Plane { n: Vector3::new(-1.0,  0.0,  0.0), d: 0.5 },
-                Plane { n: Vector3::new( 0.0,  1.0,  0.0), d: 0.5 },
-                Plane { n: Vector3::new( 0.0, -1.0,  0.0), d: 0.5 },
-                Plane { n: Vector3::new( 0.0,  0.0,  1.0), d: 0.5 },
-                Plane { n: Vector3::new( 0.0,  0.0, -1.0), d: 0.5 },
-            ];
-
-            let mut closest: Option<f32> = None;
-
-            for plane in PLANES.iter() {
-                if let Some(point) = plane.intersection(ray) {
-                    let Point3{x, y, z} = point;
-
-                    match (x, y, z) {
-                        // Intersected point must be within bounds
-                        (-0.5..=0.5, -0.5..=0.5, -0.5..=0.5) => {
-                            let diff = point - ray.origin;
-                            closest.set_if_smallest(diff.magnitude());
-                        },
-                        _ => ()
-                    }
-                }
-            }
+        self.nearest_ray_hit(ray).map(|(index, _)| (index, &self.subcubes[index]))
+    }
 
-            closest
-        }
+    /// Like `get_subcube_from_ray`, but returns where the ray first hits the
+    /// subcube instead of a reference to it.
+    pub fn get_subcube_hit_from_ray(&self, ray: &Ray3<f32>) -> Option<(usize, Point3<f32>)> {
+        self.nearest_ray_hit(ray).map(|(index, distance)| (index, ray.origin + ray.direction * distance))
+    }
 
-        struct SubcubeDistance<'a>(usize, &'a Subcube, f32);
+    /// Get every subcube that intersects with the ray, skipping unpickable
+    /// ones unless `pick_unpickable` is set.
+    /// Returns tuples of the index and the distance along the ray, sorted
+    /// nearest first, then by index.
+    pub fn get_subcubes_from_ray(&self, ray: &Ray3<f32>) -> Vec<(usize, f32)> {
+        let mut hits: Vec<(usize, f32)> = self.ray_hits(ray).collect();
+        hits.sort_by(nearest_hit_first);
+        hits
+    }
 
-        impl<'a> PartialEq for SubcubeDistance<'a> {
-            fn eq(&self, other: &SubcubeDistance) -> bool {
-                let (self_dist, other_dist) = (self.2, other.2);
-                self_dist.eq(&other_dist)
-            }
-        }
+    /// The first of `get_subcubes_from_ray`, without collecting and sorting
+    /// the rest. Picking calls this every step.
+    fn nearest_ray_hit(&self, ray: &Ray3<f32>) -> Option<(usize, f32)> {
+        self.ray_hits(ray).min_by(nearest_hit_first)
+    }
 
-        impl<'a> PartialOrd for SubcubeDistance<'a> {
-            fn partial_cmp(&self, other: &SubcubeDistance) -> Option<Ordering> {
-                let (self_dist, other_dist) = (self.2, other.2);
-                self_dist.partial_cmp(&other_dist)
-            }
-        }
+    /// The index and distance along the ray of every subcube it hits, in index order
+    fn ray_hits<'a>(&'a self, ray: &'a Ray3<f32>) -> impl Iterator<Item = (usize, f32)> + 'a {
+        self.subcubes.iter().enumerate()
+            .filter(|(_, subcube)| subcube.pickable || self.pick_unpickable)
+            .filter_map(|(index, subcube)| subcube.ray_hit_distance(ray).map(|distance| (index, distance)))
+    }
 
-        // Option tuple of: index, subcube, distance
-        let mut closest_subcube: Option<SubcubeDistance> = None;
+    /// Get the pickable subcube whose center is the smallest angle off the
+    /// ray, as seen from its origin, if that's at most `tolerance` radians.
+    /// Returns its index and the point on the ray nearest its center.
+    /// Ties go to the nearer subcube, then the lowest index.
+    pub fn get_subcube_near_ray(&self, ray: &Ray3<f32>, tolerance: f32) -> Option<(usize, Point3<f32>)> {
+        let cos_tolerance = tolerance.cos();
+        self.subcubes.iter().enumerate()
+            .filter(|(_, subcube)| subcube.pickable || self.pick_unpickable)
+            .filter_map(|(index, subcube)| {
+                let to_center = subcube.pos - ray.origin.to_vec();
+                let distance = to_center.magnitude();
+                let along = to_center.dot(ray.direction);
+                // Behind the ray, or right on its origin
+                if along <= 0.0 || distance == 0.0 {
+                    return None;
+                }
+                let cos_angle = along / distance;
+                (cos_angle >= cos_tolerance).then_some((index, cos_angle, distance, along))
+            })
+            .min_by(|(a_index, a_cos, a_dist, _), (b_index, b_cos, b_dist, _)| {
+                b_cos.total_cmp(a_cos).then(a_dist.total_cmp(b_dist)).then(a_index.cmp(b_index))
+            })
+            .map(|(index, _, _, along)| (index, ray.origin + ray.direction * along))
+    }
+}
 
-        for (index, subcube) in self.subcubes.iter().enumerate() {
-            // Transform ray relative to a non-rotated unit cube
-            let new_ray = {
-                let q = subcube.orientation.invert();
-                // Make ray relative to center of subcube
-                let origin = (ray.origin - subcube.pos) / subcube.subcube_length;
+/// Get the distance along the ray to where it enters the unit cube, if it does.
+fn intersects_with_unit_cube(ray: &Ray3<f32>) -> Option<f32> {
+    use cgmath::Point3;
+    use collision::{Continuous, Plane};
+    use crate::util::compare::CompareSmallest;
+    // The unit cube is at the origin, from -0.5..+0.5
+
+    static PLANES: [Plane<f32>; 6] = [
+        Plane { n: Vector3::new( 1.0,  0.0,  0.0), d: 0.5 },
+        Plane { n: Vector3::new(-1.0,  0.0,  0.0), d: 0.5 },
+        Plane { n: Vector3::new( 0.0,  1.0,  0.0), d: 0.5 },
+        Plane { n: Vector3::new( 0.0, -1.0,  0.0), d: 0.5 },
+        Plane { n: Vector3::new( 0.0,  0.0,  1.0), d: 0.5 },
+        Plane { n: Vector3::new( 0.0,  0.0, -1.0), d: 0.5 },
+    ];
+
+    let mut closest: Option<f32> = None;
+
+    for plane in PLANES.iter() {
+        if let Some(point) = plane.intersection(ray) {
+            let Point3{x, y, z} = point;
+
+            // Intersected point must be within bounds. Leave some slack, since
+            // the point can land a hair outside the very face it's on.
+            const EDGE: f32 = 0.5 + 1e-5;
+            if [x, y, z].iter().all(|c| (-EDGE..=EDGE).contains(c)) {
+                let diff = point - ray.origin;
+                closest.set_if_smallest(diff.magnitude());
+            }
+        }
+    }
 
-                // Rotate ray around center of subcube
-                Ray::new(q.rotate_point(origin), q.rotate_vector(ray.direction))
-            };
+    closest
+}
 
-            if let Some(dist) = intersects_with_unit_cube(&new_ray) {
-                assert!(dist >= 0.0);
-                closest_subcube.set_if_smallest(SubcubeDistance(index, subcube, dist*subcube.subcube_length));
+/// Get where the ray first crosses the surface of the axis-aligned box, if it does.
+/// Like `intersects_with_unit_cube`, a ray starting inside hits where it leaves.
+fn intersects_with_box(ray: &Ray3<f32>, center: Vector3<f32>, size: Vector3<f32>) -> Option<Point3<f32>> {
+    let (mut enter, mut leave) = (f32::NEG_INFINITY, f32::INFINITY);
+    for axis in 0..3 {
+        let (origin, direction) = (ray.origin[axis] - center[axis], ray.direction[axis]);
+        let half = size[axis] / 2.0;
+        if direction == 0.0 {
+            // Parallel to this pair of faces, so it has to start between them
+            if origin.abs() > half {
+                return None;
             }
+            continue;
         }
+        let (a, b) = ((-half - origin) / direction, (half - origin) / direction);
+        enter = enter.max(a.min(b));
+        leave = leave.min(a.max(b));
+    }
 
-        match closest_subcube {
-            Some(SubcubeDistance(idx, subcube, _)) => Some((idx, subcube)),
-            None => None
-        }
+    if leave < enter.max(0.0) {
+        return None;
+    }
+    let t = if enter >= 0.0 { enter } else { leave };
+    Some(ray.origin + ray.direction * t)
+}
+
+/// Orders ray hits by distance. Coincident subcubes, such as shards fresh
+/// from a split, are ordered by index.
+fn nearest_hit_first(&(a_index, a): &(usize, f32), &(b_index, b): &(usize, f32)) -> std::cmp::Ordering {
+    a.total_cmp(&b).then(a_index.cmp(&b_index))
+}
+
+impl Default for Cube {
+    fn default() -> Cube {
+        Cube::new()
     }
 }
 
 impl Subcube {
     fn from_segment(segment: Vector3<f32>, subcube_length: f32) -> Subcube {
         Subcube {
-            segment: segment,
-            subcube_length: subcube_length,
+            segment,
+            subcube_length,
             pos: segment,
             vel: Zero::zero(),
             orientation: Quaternion::one(),
-            angular_momentum: Zero::zero()
+            alpha: 1.0,
+            scale: Vector3::from_value(1.0),
+            pickable: true,
+            shape: SubcubeShape::Cube,
+            angular_momentum: Zero::zero(),
+            color: None,
+            outer_faces: ALL_FACES,
+            depth: 0,
+            wobble: None,
+            squash: None
+        }
+    }
+
+    /// The linear velocity, in world units per second.
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.vel
+    }
+
+    /// Replace the linear velocity. A non-finite velocity would leave the
+    /// subcube nowhere, so it's taken as zero.
+    ///
+    /// The velocity is still clamped to the cube's `max_speed` on the next step.
+    pub fn set_velocity(&mut self, vel: Vector3<f32>) {
+        self.vel = if vel.is_finite() { vel } else { Zero::zero() };
+    }
+
+    /// Add to the linear velocity. This is the change in velocity itself,
+    /// regardless of `mass()`.
+    pub fn add_impulse(&mut self, impulse: Vector3<f32>) {
+        self.set_velocity(self.vel + impulse);
+    }
+
+    /// The color drawn instead of the one from the subcube's place in the
+    /// cube, or None for that one.
+    pub fn color(&self) -> Option<Vector3<f32>> {
+        self.color
+    }
+
+    /// Which of the subcube's faces were on the outside of the original cube,
+    /// rather than cut when it split. Bit `i` is set for face `i`, in the
+    /// order +x, -x, +y, -y, +z, -z.
+    pub fn outer_faces(&self) -> u8 {
+        self.outer_faces
+    }
+
+    /// How many times the subcube has been split from the original cube,
+    /// however many ways each split was. 0 for the original cube itself.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Set the color to the one `mode` picks from `palette`.
+    pub fn apply_palette(&mut self, palette: &Palette, mode: PaletteMode) {
+        let index = mode.index(self.segment, self.depth, palette.colors().len());
+        self.set_color(Some(palette.get(index)));
+    }
+
+    /// Set an RGB color from 0 to 1 on each channel, or None to go back to
+    /// the color from the subcube's place in the cube. Channels are clamped
+    /// to that range. Subcubes split from this one inherit the color.
+    pub fn set_color(&mut self, color: Option<Vector3<f32>>) {
+        self.color = color.map(|c| c.map(|x| x.clamp(0.0, 1.0)));
+    }
+
+    /// The angular momentum, as an axis scaled by half its rate of spin in
+    /// radians per second. Orientations are quaternions, which turn by twice
+    /// the angle they're stepped by, so a length of 1 spins 2 radians per second.
+    ///
+    /// Subcubes have no moment of inertia, so this doubles as the angular velocity.
+    pub fn angular_momentum(&self) -> Vector3<f32> {
+        self.angular_momentum
+    }
+
+    /// The subcube's mass, taking the original cube's to be 1.
+    pub fn mass(&self) -> f32 {
+        self.subcube_length.powi(3)
+    }
+
+    /// `force` divided by the mass if `scale_with_mass` is set, so bigger
+    /// pieces move less.
+    fn force_for_mass(&self, force: f32, scale_with_mass: bool) -> f32 {
+        if scale_with_mass {
+            force / self.mass()
+        } else {
+            force
         }
     }
 
+    /// A rough measure of how much the subcube is moving.
+    ///
+    /// Every subcube is treated as having unit mass, not `mass()`, and unit moment of inertia
+    /// regardless of its size, so this is only meaningful relative to other
+    /// values returned by this function.
+    pub fn kinetic_energy(&self) -> f32 {
+        0.5 * self.vel.magnitude2() + 0.5 * self.angular_momentum.magnitude2()
+    }
+
     pub fn get_model_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.model_matrix_at(&self.orientation, &self.scale)
+    }
+
+    fn model_matrix_at(&self, orientation: &Quaternion<f32>, scale: &Vector3<f32>) -> cgmath::Matrix4<f32> {
         use crate::util::matrix::MatrixBuilder;
+        // Scaled after rotating, so the scale is along world axes
         cgmath::Matrix4::identity()
             .translate_v(&self.pos)
-            .scale_s(self.subcube_length)
-            .quaternion(&self.orientation)
+            .scale_v(&(scale * self.subcube_length))
+            .quaternion(orientation)
     }
 
     fn get_subdivided_subcube(&self, subdivide_count: u32, loc: (u32, u32, u32)) -> Subcube {
@@ -316,52 +1057,131 @@ impl Subcube {
 
         let interpolated_pos = new_pos(subdivide_count, loc);
 
+        // A face stays on the outside if it's on the parent's outside face
+        let last = subdivide_count - 1;
+        let (x, y, z) = loc;
+        let on_faces = [x == last, x == 0, y == last, y == 0, z == last, z == 0];
+        let outer_faces = on_faces.iter().enumerate()
+            .filter(|&(_, &on)| on)
+            .fold(0, |mask, (i, _)| mask | (1 << i)) & self.outer_faces;
+
         Subcube {
             segment: matrix_mul_v3(&segment_model, &interpolated_pos),
             subcube_length: self.subcube_length / subdivide_count as f32,
             pos: matrix_mul_v3(&model, &interpolated_pos),
             vel: self.vel,
             orientation: self.orientation,
+            alpha: self.alpha,
+            scale: self.scale,
+            pickable: self.pickable,
+            shape: self.shape,
             angular_momentum: self.angular_momentum,
+            color: self.color,
+            outer_faces,
+            depth: self.depth + 1,
+            wobble: None,
+            squash: None
         }
     }
 
     /// Add velocity and angular momentum to the subcube.
     ///
-    /// The subcube will tend to repel from the specified origin.
+    /// The subcube will tend to repel from the specified origin, weakening
+    /// with distance by `falloff`, and along `bias`.
     /// Some psudo-random variance will also be randomly added to the velocity and angular momentum.
-    pub fn hurl(&mut self, force: f32, origin: &Vector3<f32>) {
+    pub fn hurl(&mut self, force: f32, origin: &Vector3<f32>, bias: &Vector3<f32>, falloff: FalloffKind) {
         fn random_vector3() -> Vector3<f32> {
             Vector3::new(quad_rand::gen_range(-1.0, 1.0), quad_rand::gen_range(-1.0, 1.0), quad_rand::gen_range(-1.0, 1.0))
         }
 
-        let v = (self.pos - origin) * (16.0);
-        self.vel = (v + random_vector3() * (4.0)) * (force*0.1);
+        let v = falloff.push(self.pos - origin);
+        self.vel = (v + bias + random_vector3() * (4.0)) * (force*0.1);
         self.angular_momentum = (v + random_vector3() * (0.5)) * (force*0.5);
+        self.wobble = None;
+    }
+
+    /// Add velocity away from the specified origin, weakening with distance.
+    ///
+    /// Unlike `hurl`, this adds to the existing motion. Subcubes at least
+    /// `radius` away are unaffected.
+    fn shove(&mut self, force: f32, radius: f32, origin: &Vector3<f32>) {
+        let diff = self.pos - origin;
+        let dist = diff.magnitude();
+        if dist >= radius || dist == 0.0 {
+            return;
+        }
+
+        let falloff = 1.0 - dist / radius;
+        self.vel += diff / dist * (force * falloff);
     }
 
+    /// Put the subcube back in its place in the cube, at rest. Everything
+    /// but its transform and motion is kept, such as its color, opacity and
+    /// which faces are outer.
     fn reset(&mut self) {
-        *self = Subcube::from_segment(self.segment, self.subcube_length);
+        let target = Subcube::from_segment(self.segment, self.subcube_length);
+        self.pos = target.pos;
+        self.orientation = target.orientation;
+        self.scale = target.scale;
+        self.cancel_momentum();
     }
 
     fn cancel_momentum(&mut self) {
         self.vel = Zero::zero();
         self.angular_momentum = Zero::zero();
+        self.wobble = None;
+        self.squash = None;
     }
 
-    fn approach_original_arrangement(&mut self, _frac: f32) {
+    fn approach_original_arrangement(&mut self, lerp_amount: f32) {
         let target_subcube = Subcube::from_segment(self.segment, self.subcube_length);
 
-        // TODO - figure out the math on this (using frac)
-        let lerp_amount = 0.1;
-
         self.pos = self.pos.lerp(target_subcube.pos, lerp_amount);
         self.orientation = self.orientation.nlerp(target_subcube.orientation, lerp_amount);
+        self.scale = self.scale.lerp(target_subcube.scale, lerp_amount);
     }
 
-    fn step(&mut self, frac: f32) {
+    /// Push the subcube back inside the box and reflect its velocity off
+    /// each wall it crossed. Each axis is handled on its own, so crossing
+    /// several walls at once (a corner) bounces off all of them.
+    ///
+    /// The subcube is treated as a ball as wide as its sides, so a tilted
+    /// subcube's corners can poke through a little.
+    ///
+    /// If `squash` is set, hitting a wall starts a squash along its axis.
+    fn bounce_within(&mut self, min: Vector3<f32>, max: Vector3<f32>, restitution: f32, squash: bool) {
+        for axis in 0..3 {
+            let r = self.subcube_length * self.scale[axis] / 2.0;
+            let (low, high) = (min[axis] + r, max[axis] - r);
+            let speed = self.vel[axis].abs();
+            let hit = if self.pos[axis] < low {
+                self.pos[axis] = low;
+                self.vel[axis] = speed * restitution;
+                true
+            } else if self.pos[axis] > high {
+                self.pos[axis] = high;
+                self.vel[axis] = -speed * restitution;
+                true
+            } else {
+                false
+            };
+
+            if hit && squash && speed >= REST_SPEED {
+                self.squash = Some((axis, (speed / SQUASH_SPEED).min(1.0) * SQUASH_AMOUNT, 0.0));
+            }
+        }
+    }
+
+    /// `wobble` is the up axis if the subcube should wobble as it settles.
+    fn step(&mut self, frac: f32, max_speed: f32, max_angular_speed: f32, wobble: Option<Vector3<f32>>) {
         use std::f32;
-        
+        use super::physics::clamp_magnitude;
+
+        let last_vel = self.vel;
+
+        self.vel = clamp_magnitude(self.vel, max_speed);
+        self.angular_momentum = clamp_magnitude(self.angular_momentum, max_angular_speed);
+
         // **Velocity** //
         self.pos += self.vel * frac;
 
@@ -378,5 +1198,579 @@ impl Subcube {
         let m = f32::powf(0.7, frac);
         self.vel *= m;
         self.angular_momentum *= m;
+
+        self.squash = match self.squash {
+            Some((axis, amount, t)) if t + frac < SQUASH_DURATION => Some((axis, amount, t + frac)),
+            _ => None
+        };
+
+        self.wobble = match (self.wobble, wobble) {
+            (Some((axis, t)), _) if t + frac < WOBBLE_DURATION => Some((axis, t + frac)),
+            (Some(_), _) => None,
+            (None, Some(up)) if last_vel.magnitude() >= REST_SPEED && self.is_settled() => {
+                // Tip forward, the way it was going
+                let axis = up.cross(last_vel);
+                let axis = if axis.magnitude2() > 0.0 { axis.normalize() } else { Vector3::unit_x() };
+                Some((axis, 0.0))
+            },
+            (None, _) => None
+        };
+    }
+
+    /// How far along `ray` it first hits the subcube, in world units, if it does.
+    fn ray_hit_distance(&self, ray: &Ray3<f32>) -> Option<f32> {
+        // Most subcubes have never turned, such as fresh shards of an unturned
+        // cube, and testing their boxes directly skips both rotations
+        if self.orientation.v.magnitude2() <= UNROTATED_EPSILON {
+            let hit = intersects_with_box(ray, self.pos, self.scale * self.subcube_length)?;
+            Some((hit - ray.origin).magnitude())
+        } else {
+            self.rotated_ray_hit_distance(ray)
+        }
+    }
+
+    /// `ray_hit_distance` for any orientation.
+    fn rotated_ray_hit_distance(&self, ray: &Ray3<f32>) -> Option<f32> {
+        use collision::Ray;
+
+        // Transform ray relative to a non-rotated unit cube
+        let size = self.scale * self.subcube_length;
+        let q = self.orientation.invert();
+        let new_ray = {
+            // Make ray relative to center of subcube
+            let origin = (ray.origin - self.pos).to_vec().div_element_wise(size);
+
+            // Rotate ray around center of subcube
+            let direction = q.rotate_vector(ray.direction.div_element_wise(size)).normalize();
+            Ray::new(Point3::from_vec(q.rotate_vector(origin)), direction)
+        };
+
+        intersects_with_unit_cube(&new_ray).map(|dist| {
+            assert!(dist >= 0.0);
+            // Back to world space. Non-uniform scales stretch distances
+            // by direction, so go through the point that was hit.
+            let local = new_ray.origin.to_vec() + new_ray.direction * dist;
+            let hit = self.pos + self.orientation.rotate_vector(local).mul_element_wise(size);
+            (hit - ray.origin.to_vec()).magnitude()
+        })
+    }
+
+    /// Whether it's neither moving nor turning at all, so stepping it leaves it
+    /// where it is. Stricter than `is_settled`.
+    fn is_still(&self) -> bool {
+        self.vel.is_zero() && self.angular_momentum.is_zero()
+    }
+
+    /// Whether it's moving slower than `REST_SPEED`. Any wobble doesn't count.
+    pub fn is_settled(&self) -> bool {
+        self.vel.magnitude() < REST_SPEED
+    }
+
+    /// The scale to draw with, squashed if it just hit a wall.
+    fn display_scale(&self) -> Vector3<f32> {
+        match self.squash {
+            Some((axis, amount, t)) => {
+                let fade = 1.0 - t / SQUASH_DURATION;
+                let squash = amount * fade * fade;
+                // Bulge out the other ways, keeping roughly the same volume
+                let mut factor = Vector3::from_value(1.0 + squash / 2.0);
+                factor[axis] = 1.0 - squash;
+                self.scale.mul_element_wise(factor)
+            },
+            None => self.scale
+        }
+    }
+
+    /// The orientation to draw it at, including any wobble.
+    fn display_orientation(&self) -> Quaternion<f32> {
+        match self.wobble {
+            Some((axis, t)) => {
+                let fade = 1.0 - t / WOBBLE_DURATION;
+                let angle = WOBBLE_ANGLE * fade * fade * (2.0 * std::f32::consts::PI * WOBBLE_FREQUENCY * t).sin();
+                Quaternion::from_axis_angle(axis, cgmath::Rad(angle)) * self.orientation
+            },
+            None => self.orientation
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const FRAC: f32 = 1.0 / 60.0;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        HurlAll(f32),
+        Explode(usize, f32),
+        Step(u32)
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0.0f32..8.0).prop_map(Op::HurlAll),
+            (any::<usize>(), 0.0f32..8.0).prop_map(|(i, force)| Op::Explode(i, force)),
+            (1u32..30).prop_map(Op::Step),
+        ]
+    }
+
+    fn apply(cube: &mut Cube, ops: &[Op]) {
+        for op in ops {
+            match *op {
+                Op::HurlAll(force) => cube.try_hurl_all(force),
+                Op::Explode(i, force) => {
+                    let index = i % cube.subcubes.len();
+                    cube.explode_subcube_if_at_least(index, force, 2, 1.0/16.0);
+                },
+                Op::Step(n) => for _ in 0..n { cube.step(FRAC); }
+            }
+        }
+    }
+
+    /// Step long enough for a rearrange or reset to finish.
+    fn settle(cube: &mut Cube) {
+        let steps = (cube.rearrange_duration / FRAC).ceil() as u32 + 2;
+        for _ in 0..steps {
+            cube.step(FRAC);
+        }
+    }
+
+    fn assert_at_rest(subcube: &Subcube) {
+        assert_eq!(subcube.pos, subcube.segment);
+        assert_eq!(subcube.orientation, Quaternion::one());
+        assert_eq!(subcube.velocity(), Vector3::zero());
+        assert_eq!(subcube.angular_momentum(), Vector3::zero());
+    }
+
+    #[test]
+    fn palette_colors_faces_and_shards_inherit_them() {
+        let palette = Palette::rubiks();
+        let mut cube = Cube::new_subdivided(3);
+        cube.apply_palette(&palette, PaletteMode::ByFace);
+        for color in palette.colors() {
+            assert!(cube.subcubes.iter().any(|s| s.color() == Some(*color)));
+        }
+
+        let color = cube.subcubes[0].color();
+        cube.explode_subcube(0, 1.0, 2);
+        let shards: Vec<_> = cube.subcubes.iter().filter(|s| s.subcube_length < 1.0 / 3.0).collect();
+        assert_eq!(shards.len(), 8);
+        assert!(shards.iter().all(|s| s.color() == color));
+    }
+
+    #[test]
+    fn only_faces_on_the_original_outside_are_outer() {
+        let cube = Cube::new_subdivided(3);
+        let count = |faces: u32| cube.subcubes.iter().filter(|s| s.outer_faces().count_ones() == faces).count();
+        // Corners, edges, face centers and the very middle
+        assert_eq!([count(3), count(2), count(1), count(0)], [8, 12, 6, 1]);
+
+        let corner = cube.subcubes.iter().find(|s| s.segment.x > 0.0 && s.segment.y < 0.0 && s.segment.z > 0.0).unwrap();
+        assert_eq!(corner.outer_faces(), 0b01_1001);
+
+        let mut cube = Cube::new();
+        cube.explode_subcube(0, 1.0, 2);
+        cube.explode_subcube(0, 1.0, 2);
+        assert!(cube.subcubes.iter().all(|s| s.outer_faces().count_ones() == 3 || s.subcube_length == 0.25));
+        assert!(cube.subcubes.iter().any(|s| s.outer_faces().count_ones() == 1));
+    }
+
+    #[test]
+    fn depth_counts_splits_of_any_size() {
+        let mut cube = Cube::new_subdivided(3);
+        assert!(cube.subcubes.iter().all(|s| s.depth() == 1));
+        cube.explode_subcube(0, 1.0, 3);
+        cube.crack_subcube(0, 2, 0.0);
+        assert_eq!(cube.subcubes[0].depth(), 3);
+        assert_eq!(cube.subcubes.iter().filter(|s| s.depth() == 2).count(), 26);
+        assert_eq!(cube.subcubes.iter().filter(|s| s.depth() == 3).count(), 8);
+    }
+
+    #[test]
+    fn rearranging_keeps_the_outer_faces() {
+        let mut cube = Cube::new();
+        cube.explode_subcube(0, 1.0, 2);
+        let outer_faces: Vec<_> = cube.subcubes.iter().map(|s| s.outer_faces()).collect();
+        assert!(outer_faces.iter().all(|&faces| faces != ALL_FACES));
+
+        cube.try_rearrange();
+        settle(&mut cube);
+        assert_eq!(cube.subcubes.iter().map(|s| s.outer_faces()).collect::<Vec<_>>(), outer_faces);
+        for subcube in cube.subcubes.iter() {
+            assert_at_rest(subcube);
+        }
+    }
+
+    #[test]
+    fn rearranging_keeps_the_opacity() {
+        let mut cube = Cube::new_subdivided(2);
+        cube.subcubes[0].alpha = 0.5;
+        cube.try_hurl_all(4.0);
+        cube.try_rearrange();
+        settle(&mut cube);
+        assert_eq!(cube.subcubes[0].alpha, 0.5);
+        assert!(cube.subcubes[1..].iter().all(|s| s.alpha == 1.0));
+    }
+
+    #[test]
+    fn cracked_shards_stay_in_place() {
+        let mut cube = Cube::new();
+        cube.subcubes[0].set_velocity(Vector3::unit_x());
+        assert!(cube.crack_subcube(0, 2, 0.75));
+        assert_eq!(cube.subcubes.len(), 8);
+        for subcube in cube.subcubes.iter() {
+            assert_eq!(subcube.velocity(), Vector3::zero());
+            assert_eq!(subcube.angular_momentum(), Vector3::zero());
+            assert_eq!(subcube.pos, subcube.segment);
+        }
+
+        assert!(!cube.crack_subcube(0, 2, 0.75));
+        assert_eq!(cube.subcubes.len(), 8);
+    }
+
+    #[test]
+    fn the_version_changes_only_when_subcubes_might_have() {
+        let mut cube = Cube::new_subdivided(2);
+        let version = cube.version();
+        cube.step(FRAC);
+        cube.arm_explosion(0, 1.0, 1.0, 2, 0.0);
+        assert_eq!(cube.version(), version);
+
+        cube.try_hurl_all(1.0);
+        let hurled = cube.version();
+        assert_ne!(hurled, version);
+        cube.step(FRAC);
+        assert_ne!(cube.version(), hurled);
+
+        cube.try_freeze();
+        let frozen = cube.version();
+        cube.step(FRAC);
+        assert_eq!(cube.version(), frozen);
+    }
+
+    #[test]
+    fn the_magnet_pulls_in_only_what_is_near() {
+        let mut cube = Cube::new_subdivided(2);
+        cube.magnet = Some((Vector3::new(2.0, 0.0, 0.0), 10.0));
+        cube.magnet_radius = 2.0;
+        let before: Vec<_> = cube.subcubes.iter().map(|s| s.pos).collect();
+        cube.step(FRAC);
+
+        for (subcube, before) in cube.subcubes.iter().zip(before) {
+            let distance = (before - Vector3::new(2.0, 0.0, 0.0)).magnitude();
+            if distance < 2.0 {
+                assert!(subcube.velocity().x > 0.0);
+            } else {
+                assert_eq!(subcube.velocity(), Vector3::zero());
+            }
+        }
+    }
+
+    #[test]
+    fn unrotated_picks_match_the_general_path() {
+        quad_rand::srand(3);
+        let mut subcube = Subcube::from_segment(Vector3::new(0.2, -0.1, 0.3), 0.5);
+        subcube.scale = Vector3::new(1.0, 1.5, 0.5);
+        let r = |range: f32| quad_rand::gen_range(-range, range);
+
+        let (mut hits, mut inside) = (0, 0);
+        for i in 0..500 {
+            // Some rays start inside, where both hit the face they leave through
+            let origin = match i % 5 {
+                0 => subcube.pos + Vector3::new(r(0.2), r(0.3), r(0.1)),
+                _ => Vector3::new(r(3.0), r(3.0), r(3.0))
+            };
+            let target = subcube.pos + Vector3::new(r(0.5), r(0.5), r(0.5));
+            let ray = Ray3::new(Point3::from_vec(origin), (target - origin).normalize());
+
+            let (fast, general) = (subcube.ray_hit_distance(&ray), subcube.rotated_ray_hit_distance(&ray));
+            match (fast, general) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-4, "{} != {} for {:?}", a, b, ray),
+                (None, None) => {},
+                _ => panic!("Only one path hit {:?}: {:?} vs {:?}", ray, fast, general)
+            }
+            hits += fast.is_some() as u32;
+            inside += (i % 5 == 0 && fast.is_some()) as u32;
+        }
+        assert!(hits > 100 && inside == 100);
+    }
+
+    #[test]
+    fn culling_keeps_the_rest_in_order_and_their_fuses() {
+        let mut cube = Cube::new_subdivided(2);
+        let upper: Vec<_> = cube.subcubes.iter().filter(|s| s.pos.y > 0.0).map(|s| s.segment).collect();
+        let lower_armed = cube.subcubes.iter().position(|s| s.pos.y < 0.0).unwrap();
+        let upper_armed = cube.subcubes.iter().rposition(|s| s.pos.y > 0.0).unwrap();
+        cube.arm_explosion(lower_armed, 1.0, 1.0, 2, 0.0);
+        cube.arm_explosion(upper_armed, 1.0, 1.0, 2, 0.0);
+        let (version, generation) = (cube.version(), cube.generation());
+
+        let map = cube.cull_below(0.0);
+        assert_ne!(cube.version(), version);
+        assert_ne!(cube.generation(), generation);
+        assert_eq!(cube.subcubes.iter().map(|s| s.segment).collect::<Vec<_>>(), upper);
+        assert_eq!(map[lower_armed], None);
+        assert_eq!(map[upper_armed], Some(3));
+        assert_eq!(cube.fuse_remaining(3), Some(1.0));
+        assert_eq!(cube.armed.len(), 1);
+
+        // Resetting still brings everything back
+        cube.try_reset();
+        while cube.state_kind() != CubeStateKind::Simulating || cube.subcubes.len() < 8 {
+            cube.step(FRAC);
+        }
+        assert_eq!(cube.subcubes.len(), 8);
+    }
+
+    #[test]
+    fn armed_explosions_go_off_after_their_fuses() {
+        let mut cube = Cube::new_subdivided(2);
+        cube.arm_explosion(0, 10.0 * FRAC, 1.0, 2, 0.0);
+        cube.arm_explosion(1, 20.0 * FRAC, 1.0, 2, 0.0);
+        cube.arm_explosion(1, 5.0 * FRAC, 1.0, 2, 0.0);
+        assert_eq!(cube.fuse_remaining(1), Some(5.0 * FRAC));
+        assert_eq!(cube.fuse_remaining(2), None);
+        assert_eq!(cube.fuses().collect::<Vec<_>>(), [(0, 10.0 * FRAC), (1, 20.0 * FRAC), (1, 5.0 * FRAC)]);
+
+        let detonated: Vec<_> = (0..20).flat_map(|_| cube.step(FRAC)).map(|d| (d.index, d.split)).collect();
+        assert_eq!(detonated, [(1, true), (0, true), (1, true)]);
+        assert_eq!(cube.subcubes.len(), 8 + 3 * 7);
+        assert_eq!(cube.fuse_remaining(0), None);
+    }
+
+    #[test]
+    fn rearranging_disarms_explosions() {
+        let mut cube = Cube::new();
+        cube.arm_explosion(0, FRAC, 1.0, 2, 0.0);
+        cube.try_rearrange();
+        assert_eq!(cube.fuse_remaining(0), None);
+
+        // Nor can any be armed until it's simulating again
+        cube.arm_explosion(0, FRAC, 1.0, 2, 0.0);
+        while cube.state_kind() != CubeStateKind::Simulating {
+            assert!(cube.step(FRAC).is_empty());
+        }
+        assert_eq!(cube.subcubes.len(), 1);
+    }
+
+    #[test]
+    fn setters_keep_subcubes_valid() {
+        let mut cube = Cube::new_subdivided(2);
+        cube.for_each_subcube_mut(|subcube| {
+            subcube.set_velocity(Vector3::new(f32::NAN, 0.0, 0.0));
+            subcube.add_impulse(Vector3::unit_y());
+            subcube.set_color(Some(Vector3::new(2.0, 0.5, -1.0)));
+        });
+
+        for subcube in cube.subcubes.iter() {
+            assert_eq!(subcube.velocity(), Vector3::unit_y());
+            assert_eq!(subcube.color(), Some(Vector3::new(1.0, 0.5, 0.0)));
+        }
+
+        // Rearranging stops them, but the colors stay
+        cube.try_rearrange();
+        settle(&mut cube);
+        for subcube in cube.subcubes.iter() {
+            assert_at_rest(subcube);
+            assert_eq!(subcube.color(), Some(Vector3::new(1.0, 0.5, 0.0)));
+        }
+    }
+
+    #[test]
+    fn a_strong_bias_hurls_everything_its_way() {
+        let mut cube = Cube::new_subdivided(3);
+        cube.hurl_bias = Vector3::unit_y() * 100.0;
+        cube.try_hurl_all(4.0);
+
+        for subcube in cube.subcubes.iter() {
+            assert!(subcube.velocity().y > 0.0);
+        }
+    }
+
+    #[test]
+    fn bounds_bounce_off_a_corner() {
+        let mut cube = Cube::new();
+        cube.bounds = Some(Cube::default_bounds());
+        cube.restitution = 0.5;
+        // Reaches the corner in about 5 steps, and is well clear of the other walls by 10
+        cube.subcubes[0].set_velocity(Vector3::new(20.0, -20.0, 0.0));
+        for _ in 0..10 {
+            cube.step(FRAC);
+        }
+
+        let (min, max) = Cube::default_bounds();
+        let subcube = &cube.subcubes[0];
+        for axis in 0..3 {
+            assert!(subcube.pos[axis] >= min[axis] + 0.5 && subcube.pos[axis] <= max[axis] - 0.5);
+        }
+        // Both walls of the corner sent it back
+        assert!(subcube.velocity().x < 0.0 && subcube.velocity().y > 0.0);
+    }
+
+    #[test]
+    fn falloffs_agree_at_the_matching_distance() {
+        let near = Vector3::unit_x() * 0.1;
+        let matching = Vector3::unit_x() * FalloffKind::MATCHING_DISTANCE;
+        let far = Vector3::unit_x() * 2.0;
+        let kinds = [FalloffKind::Linear, FalloffKind::InverseSquare, FalloffKind::Constant];
+
+        for kind in kinds {
+            assert!((kind.push(matching) - FalloffKind::Linear.push(matching)).magnitude() < 1e-5);
+            assert_eq!(kind.push(Vector3::zero()), Vector3::zero());
+        }
+        assert!(FalloffKind::InverseSquare.push(near).x > FalloffKind::Constant.push(near).x);
+        assert!(FalloffKind::InverseSquare.push(far).x < FalloffKind::Constant.push(far).x);
+        assert!(FalloffKind::Linear.push(far).x > FalloffKind::Constant.push(far).x);
+    }
+
+    #[test]
+    fn explosions_stop_splitting_at_the_minimum_length() {
+        let mut cube = Cube::new();
+        cube.set_min_subcube_length(0.75);
+
+        cube.explode_subcube(0, 4.0, 2);
+        assert_eq!(cube.subcubes.len(), 8);
+        cube.explode_subcube(0, 4.0, 2);
+        assert_eq!(cube.subcubes.len(), 8);
+        assert!(cube.subcubes[0].velocity().magnitude() > 0.0);
+
+        // An explicit minimum overrides the cube's
+        cube.explode_subcube_if_at_least(0, 4.0, 2, 0.25);
+        assert_eq!(cube.subcubes.len(), 15);
+    }
+
+    #[test]
+    fn settling_subcubes_wobble_without_moving() {
+        let mut cube = Cube::new();
+        cube.settle_wobble = true;
+        // Slow enough to settle on the next step
+        cube.subcubes[0].set_velocity(Vector3::unit_x() * REST_SPEED * 1.001);
+        assert!(!cube.is_settled());
+        cube.step(FRAC);
+        assert!(cube.is_settled());
+
+        // Drawn tipped over, but sitting just where it stopped
+        let subcube = cube.subcubes[0];
+        for _ in 0..5 {
+            cube.step(FRAC);
+        }
+        let drawn = cube.iter_render().next().unwrap().model;
+        assert_ne!(drawn, cube.subcubes[0].get_model_matrix());
+        assert_eq!(cube.subcubes[0].orientation, subcube.orientation);
+        assert!(cube.is_settled());
+
+        for _ in 0..(WOBBLE_DURATION / FRAC) as u32 {
+            cube.step(FRAC);
+        }
+        assert_eq!(cube.iter_render().next().unwrap().model, cube.subcubes[0].get_model_matrix());
+    }
+
+    #[test]
+    fn subcubes_squash_against_walls_and_spring_back() {
+        let mut cube = Cube::new();
+        cube.squash_on_impact = true;
+        cube.bounds = Some((Vector3::from_value(-1.0), Vector3::from_value(1.0)));
+        cube.subcubes[0].pos = Vector3::new(0.0, -0.45, 0.0);
+        cube.subcubes[0].set_velocity(-Vector3::unit_y() * SQUASH_SPEED);
+        cube.step(FRAC);
+
+        // Drawn flatter along the wall's axis and wider the other ways
+        let scale = cube.subcubes[0].display_scale();
+        assert!(scale.y < 1.0 && scale.x > 1.0 && scale.z > 1.0);
+        assert_eq!(cube.subcubes[0].scale, Vector3::from_value(1.0));
+
+        for _ in 0..(SQUASH_DURATION / FRAC) as u32 + 1 {
+            cube.step(FRAC);
+        }
+        assert_eq!(cube.iter_render().next().unwrap().model, cube.subcubes[0].get_model_matrix());
+    }
+
+    #[test]
+    fn heavier_subcubes_are_hurled_slower() {
+        let mut cube = Cube::new();
+        cube.motion_scales_with_mass = true;
+        cube.explode_subcube(0, 4.0, 2);
+        cube.explode_subcube(0, 4.0, 2);
+
+        // The smallest pieces must be the fastest, on average
+        let mean_speed = |length: f32| {
+            let speeds: Vec<_> = cube.subcubes.iter()
+                .filter(|s| s.subcube_length == length)
+                .map(|s| s.velocity().magnitude())
+                .collect();
+            speeds.iter().sum::<f32>() / speeds.len() as f32
+        };
+        assert_eq!(cube.subcubes[0].mass(), 0.25f32.powi(3));
+        assert!(mean_speed(0.25) > mean_speed(0.5));
+    }
+
+    proptest! {
+        #[test]
+        fn freezing_stops_everything_in_place(ops in prop::collection::vec(op(), 0..12)) {
+            let mut cube = Cube::new();
+            apply(&mut cube, &ops);
+            let poses: Vec<_> = cube.subcubes.iter().map(|s| (s.pos, s.orientation)).collect();
+
+            cube.try_freeze();
+            settle(&mut cube);
+
+            for (subcube, &(pos, orientation)) in cube.subcubes.iter().zip(poses.iter()) {
+                prop_assert_eq!(subcube.pos, pos);
+                // Renormalizing every step can nudge the last bits
+                prop_assert!((subcube.orientation - orientation).magnitude() < 1e-5);
+                prop_assert_eq!(subcube.velocity(), Vector3::zero());
+                prop_assert_eq!(subcube.angular_momentum(), Vector3::zero());
+            }
+        }
+
+        #[test]
+        fn reset_returns_to_a_single_cube(ops in prop::collection::vec(op(), 0..12)) {
+            let mut cube = Cube::new();
+            apply(&mut cube, &ops);
+
+            cube.try_reset();
+            settle(&mut cube);
+
+            prop_assert_eq!(cube.subcubes.len(), 1);
+            let subcube = &cube.subcubes[0];
+            prop_assert_eq!(subcube.segment, Vector3::zero());
+            prop_assert_eq!(subcube.subcube_length, 1.0);
+            assert_at_rest(subcube);
+        }
+
+        #[test]
+        fn reset_returns_to_the_initial_subdivision(n in 1u32..5, ops in prop::collection::vec(op(), 0..12)) {
+            let mut cube = Cube::new_subdivided(n);
+            let initial: Vec<_> = cube.subcubes.iter().map(|s| (s.segment, s.subcube_length)).collect();
+            prop_assert_eq!(initial.len(), n.pow(3) as usize);
+
+            apply(&mut cube, &ops);
+            cube.try_reset();
+            settle(&mut cube);
+
+            let reset: Vec<_> = cube.subcubes.iter().map(|s| (s.segment, s.subcube_length)).collect();
+            prop_assert_eq!(reset, initial);
+            for subcube in cube.subcubes.iter() {
+                assert_at_rest(subcube);
+            }
+        }
+
+        #[test]
+        fn rearrange_returns_to_the_original_grid(ops in prop::collection::vec(op(), 0..12)) {
+            let mut cube = Cube::new();
+            apply(&mut cube, &ops);
+            let segments: Vec<_> = cube.subcubes.iter().map(|s| (s.segment, s.subcube_length)).collect();
+
+            cube.try_rearrange();
+            settle(&mut cube);
+
+            let rearranged: Vec<_> = cube.subcubes.iter().map(|s| (s.segment, s.subcube_length)).collect();
+            prop_assert_eq!(rearranged, segments);
+            for subcube in cube.subcubes.iter() {
+                assert_at_rest(subcube);
+            }
+        }
     }
 }
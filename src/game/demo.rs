@@ -0,0 +1,148 @@
+//! Scripted input for running the demo unattended.
+
+use super::{GameInput, GameState};
+use super::action::Action;
+
+/// An input to apply for one step, followed by some idle steps.
+#[derive(Clone)]
+pub struct DemoAction {
+    pub input: GameInput,
+    /// Steps to wait after applying `input`, before the next action
+    pub wait_steps: u32
+}
+
+impl DemoAction {
+    /// Apply the input, then wait for `seconds`.
+    pub fn new(input: GameInput, seconds: f32) -> DemoAction {
+        DemoAction {
+            input,
+            wait_steps: (seconds * GameState::steps_per_second() as f32).round() as u32
+        }
+    }
+
+    /// Do just `action`, then wait for `seconds`.
+    pub fn press(action: Action, seconds: f32) -> DemoAction {
+        let mut input = GameInput::new();
+        *action.flag(&mut input) = true;
+        DemoAction::new(input, seconds)
+    }
+}
+
+/// A looping sequence of timed actions, advanced one game step at a time.
+///
+/// Real user input interrupts the script, which then waits `pause_steps`
+/// without input before carrying on where it left off.
+pub struct DemoScript {
+    actions: Vec<DemoAction>,
+    pause_steps: u32,
+    index: usize,
+    waited: u32,
+    paused_for: u32
+}
+
+impl DemoScript {
+    /// # Panics
+    /// Panics if `actions` is empty.
+    pub fn new(actions: Vec<DemoAction>, pause_seconds: f32) -> DemoScript {
+        assert!(!actions.is_empty());
+
+        DemoScript {
+            actions,
+            pause_steps: (pause_seconds * GameState::steps_per_second() as f32).round() as u32,
+            index: 0,
+            waited: 0,
+            paused_for: 0
+        }
+    }
+
+    /// Spin the view, explode the middle a couple of times, hurl everything,
+    /// then pull it back together and reset.
+    pub fn reel() -> DemoScript {
+        let center = Some((0.0, 0.0));
+
+        let actions = vec![
+            DemoAction::new(GameInput { rotate_view: (0.05, 0.02), ..GameInput::new() }, 2.0),
+            DemoAction::new(GameInput { explode_subcube: true, pointer: center, ..GameInput::new() }, 1.5),
+            DemoAction::new(GameInput { explode_subcube: true, pointer: center, ..GameInput::new() }, 1.5),
+            DemoAction::press(Action::HurlAll, 3.0),
+            DemoAction::press(Action::Rearrange, 3.0),
+            DemoAction::new(GameInput { rotate_view: (-0.05, 0.0), ..GameInput::new() }, 1.0),
+            DemoAction::press(Action::HurlAll, 2.0),
+            DemoAction::press(Action::Reset, 3.0),
+        ];
+
+        DemoScript::new(actions, 10.0)
+    }
+
+    /// Stop scripting until there's been no user input for a while.
+    pub fn interrupt(&mut self) {
+        self.paused_for = self.pause_steps;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_for > 0
+    }
+
+    /// Get the scripted input for the next step, or None while paused.
+    pub fn next_input(&mut self) -> Option<GameInput> {
+        if self.paused_for > 0 {
+            self.paused_for -= 1;
+            return None;
+        }
+
+        let action = &self.actions[self.index];
+        let input = if self.waited == 0 { action.input.clone() } else { GameInput::new() };
+
+        if self.waited >= action.wait_steps {
+            self.waited = 0;
+            self.index = (self.index + 1) % self.actions.len();
+        } else {
+            self.waited += 1;
+        }
+
+        Some(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script() -> DemoScript {
+        let actions = vec![
+            DemoAction { input: GameInput { hurl_all: true, ..GameInput::new() }, wait_steps: 1 },
+            DemoAction { input: GameInput { reset: true, ..GameInput::new() }, wait_steps: 0 },
+        ];
+        DemoScript::new(actions, 2.0 / GameState::steps_per_second() as f32)
+    }
+
+    /// Summarize an input as the action it carries.
+    fn name(input: GameInput) -> &'static str {
+        match (input.hurl_all, input.reset) {
+            (true, _) => "hurl",
+            (_, true) => "reset",
+            _ => "wait"
+        }
+    }
+
+    #[test]
+    fn actions_wait_and_loop() {
+        let mut script = script();
+        let names: Vec<_> = (0..6).map(|_| name(script.next_input().unwrap())).collect();
+        assert_eq!(names, ["hurl", "wait", "reset", "hurl", "wait", "reset"]);
+    }
+
+    #[test]
+    fn interrupting_pauses_then_resumes_in_place() {
+        let mut script = script();
+        assert_eq!(name(script.next_input().unwrap()), "hurl");
+
+        script.interrupt();
+        assert!(script.next_input().is_none());
+        assert!(script.next_input().is_none());
+        assert!(!script.is_paused());
+
+        assert_eq!(name(script.next_input().unwrap()), "wait");
+        assert_eq!(name(script.next_input().unwrap()), "reset");
+    }
+}
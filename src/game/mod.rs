@@ -1,20 +1,276 @@
 pub mod cube;
+pub mod demo;
+pub mod attract;
+pub mod action;
+pub mod quality;
+pub mod palette;
+pub mod sim_log;
 mod physics;
 
 use cgmath::prelude::*;
 use cgmath::{Vector3, Vector4, Point3};
-use collision::Ray;
+use collision::{Ray, Ray3};
 use crate::util::matrix::MatrixBuilder;
 
-use self::cube::Cube;
+use self::cube::{Cube, Detonation};
+use self::palette::{Palette, PaletteMode};
+use self::action::Action;
 
 /// GameState describes all non-derivable data required to present a frame.
 /// It is perpetually updated and controlled by the game loop.
 pub struct GameState {
     pub cube: cube::Cube,
-    pub show_outlines: bool,
+    pub outline_mode: OutlineMode,
+    /// Skip drawing the inward-facing sides of subcubes.
+    /// Turning this off helps when the camera ends up inside the debris.
+    pub cull_faces: bool,
+    /// If true, the view keeps spinning after it's let go.
+    /// If false, it stops as soon as it's released.
+    pub orbit_inertia: bool,
+    /// Draw faint copies of the subcubes where they belong in the cube.
+    pub show_ghost: bool,
+    pub shadow_mode: ShadowMode,
+    /// Report the picking ray in `GameStepResult::debug_ray`.
+    pub debug_picking: bool,
+    /// Label each subcube with its index, to check picking against.
+    pub show_indices: bool,
+    /// The colors `palette_mode` hands out.
+    pub palette: Palette,
+    /// How subcubes are colored from `palette`, or None for the colors from
+    /// their place in the cube.
+    pub palette_mode: Option<PaletteMode>,
+    /// Drawn on the faces exposed when subcubes split, rather than the ones
+    /// from the original cube's surface. None draws them like any other face.
+    pub inner_color: Option<Vector3<f32>>,
+    /// Seconds between explosions while the explode button is held, so
+    /// dragging over the cube carves it up at a steady pace.
+    pub drill_interval: f32,
+    /// Seconds since the last explosion while the explode button is held,
+    /// or None if it's up or hasn't exploded anything since it was pressed.
+    drill_time: Option<f32>,
+    /// The least seconds between any two explosions, however they're triggered.
+    /// Keeps rapid clicking from exploding more often on faster machines.
+    pub explode_cooldown: f32,
+    time_since_explosion: f32,
+    /// How many ways an exploded subcube splits on each axis.
+    pub explode_subdivisions: u32,
+    /// Seconds between clicking a subcube and it exploding, flashing all
+    /// the while. None explodes it right away.
+    pub explode_fuse: Option<f32>,
+    /// How hard hurls and explosions are biased away from the viewer, along
+    /// `camera_forward`. Set as `Cube::hurl_bias` every step, so it follows
+    /// the view. None, the default, leaves `Cube::hurl_bias` alone.
+    pub blast_away_from_viewer: Option<f32>,
+    /// Where the magnet is pulling subcubes toward and how hard, while
+    /// `GameInput::magnet` is held. Set by `step`, and passed on as `Cube::magnet`.
+    pub magnet: Option<(Vector3<f32>, f32)>,
+    /// How much speed the magnet adds per second, at its middle
+    pub magnet_strength: f32,
+    pub shading_mode: ShadingMode,
+    pub render_style: RenderStyle,
+    /// How much darker the edges of each face are than its middle, from 0
+    /// to 1, so neighboring subcubes stand apart. 0, the default, leaves
+    /// faces flat.
+    pub edge_darkening: f32,
+    /// How much of the last frame is cleared before drawing the next, from
+    /// 0 to 1. 1 clears it all, and anything less leaves trails behind
+    /// moving subcubes.
+    pub trail_fade: f32,
+    /// Treat colors as linear light and encode them as sRGB as they're
+    /// drawn, the way a gamma-correct renderer would. Midtones come out
+    /// lighter than with it off, when colors are written out as they are.
+    pub srgb_output: bool,
+    /// A PNG image to map onto the faces of the cube, or None for plain faces.
+    /// Shards show the part of the image from where they were in the original cube.
+    pub texture_path: Option<std::path::PathBuf>,
+    /// Generate mipmaps for the texture, so that distant shards don't shimmer.
+    pub texture_mipmaps: bool,
+    /// Angular momentum to gently spin the view with while idle, such as
+    /// `IDLE_SPIN`. None, the default, disables the idle spin.
+    pub idle_spin: Option<Vector3<f32>>,
+    /// Seconds without any view rotation before the idle spin kicks in.
+    pub idle_timeout: f32,
+    idle_time: f32,
+    /// Vertical field of view, in degrees.
+    pub fovy: f32,
+    /// The projection the view is in, or easing toward.
+    pub projection_mode: ProjectionMode,
+    /// How far the view is from perspective (0) to orthographic (1)
+    ortho_blend: f32,
+    /// Distance to the near clipping plane.
+    pub near: f32,
+    /// Distance to the far clipping plane.
+    pub far: f32,
     orientation: physics::QuaternionMotion<f32>,
-    zoom: physics::ScalarMotion<f32>
+    zoom: physics::ScalarMotion<f32>,
+    /// The point the view orbits around
+    pivot: physics::VectorApproach<f32>,
+    /// An on-screen aim that picks in place of `GameInput::pointer`, for
+    /// picking without a mouse. Ranges like the pointer, from -1 to +1.
+    pub reticle: Option<(f32, f32)>,
+    /// Zoom toward the point under the pointer, rather than the pivot.
+    pub zoom_to_pointer: bool,
+    /// How far off the pointer a subcube can be and still get picked, in
+    /// radians, for when the pointer misses everything. Tiny shards are
+    /// easier to click with a little. 0 picks only what's right under it.
+    pub pick_tolerance: f32,
+    /// Where the last zoom started over, kept still on screen while zooming
+    zoom_anchor: Option<Vector3<f32>>,
+    /// If true, the orientation and zoom are easing back to where they started
+    resetting_camera: bool,
+    /// What the random numbers were seeded with, if anything.
+    /// Only kept to be saved; setting it doesn't reseed anything.
+    pub seed: Option<u64>,
+    /// Which way is up, as a unit vector. Y by default. See `set_up_axis`.
+    up_axis: Vector3<f32>,
+    /// Given the cube after every step, to record it for plotting. None by default.
+    pub sim_logger: Option<sim_log::SimLogger>,
+    /// The subcube picked out with `select_next` and `select_prev`, and the
+    /// `Cube::generation` its index is into
+    selected: Option<(usize, u64)>,
+    /// The last pick, for when neither the pointer's ray nor the cube has changed since
+    pick_cache: Option<PickCache>,
+    /// Actions from `apply_action`, for the next step
+    queued_actions: Vec<Action>,
+    /// The `palette_mode`, `Cube::generation` and subcube count as of when
+    /// the palette was last applied, so it's only applied again once
+    /// subcubes split or are replaced
+    palette_applied: Option<(PaletteMode, u64, usize)>
+}
+
+/// A slow turn about the vertical axis, for `GameState::idle_spin`
+pub const IDLE_SPIN: Vector3<f32> = Vector3::new(0.0, 0.2, 0.0);
+
+// How much of the way back to the initial camera is covered every second
+const CAMERA_RESET_RATE: f32 = 0.99;
+const INITIAL_ZOOM: f32 = 0.5;
+
+/// The camera's starting orientation for a Y-up scene
+fn initial_orientation() -> cgmath::Quaternion<f32> {
+    Rotation::look_at(Vector3::new(0.5, 0.25, 0.5), Vector3::new(0.0, 1.0, 0.0))
+}
+
+/// A picking ray and what it hit, as of a `Cube::version`.
+#[derive(Copy, Clone)]
+struct PickCache {
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    version: u64,
+    pick_unpickable: bool,
+    tolerance: f32,
+    hit: Option<(usize, Point3<f32>)>
+}
+
+/// How the renderer outlines the edges of subcubes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutlineMode {
+    Off,
+    /// Darken each subcube's own edges
+    Geometry,
+    /// Find edges in the rendered depth, after the scene is drawn
+    ScreenSpace
+}
+
+impl OutlineMode {
+    pub fn next(self) -> OutlineMode {
+        match self {
+            OutlineMode::Geometry => OutlineMode::ScreenSpace,
+            OutlineMode::ScreenSpace => OutlineMode::Off,
+            OutlineMode::Off => OutlineMode::Geometry
+        }
+    }
+}
+
+/// How the renderer shadows the subcubes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShadowMode {
+    None,
+    /// Darken the floor under each subcube with a flattened copy of it
+    Blob,
+    /// Render depth from the light, and shadow the subcubes with it
+    Mapped
+}
+
+impl ShadowMode {
+    pub fn next(self) -> ShadowMode {
+        match self {
+            ShadowMode::None => ShadowMode::Blob,
+            ShadowMode::Blob => ShadowMode::Mapped,
+            ShadowMode::Mapped => ShadowMode::None
+        }
+    }
+}
+
+/// How the renderer colors each subcube.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShadingMode {
+    /// Color by the subcube's original position within the cube
+    Normal,
+    /// Color by distance from the origin
+    Depth,
+    /// Color by linear speed
+    Speed
+}
+
+impl ShadingMode {
+    pub fn next(self) -> ShadingMode {
+        match self {
+            ShadingMode::Normal => ShadingMode::Depth,
+            ShadingMode::Depth => ShadingMode::Speed,
+            ShadingMode::Speed => ShadingMode::Normal
+        }
+    }
+}
+
+/// Seconds to ease between perspective and orthographic projection
+const PROJECTION_TRANSITION_TIME: f32 = 0.4;
+
+/// How the view projects the scene onto the screen.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ProjectionMode {
+    Perspective,
+    /// No foreshortening. Frames what the perspective view frames at the pivot.
+    Orthographic
+}
+
+impl ProjectionMode {
+    pub fn next(self) -> ProjectionMode {
+        match self {
+            ProjectionMode::Perspective => ProjectionMode::Orthographic,
+            ProjectionMode::Orthographic => ProjectionMode::Perspective
+        }
+    }
+}
+
+/// What the renderer draws each subcube as.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RenderStyle {
+    /// Full cube or sphere geometry
+    Solid,
+    /// A single point at its position, as big as the subcube.
+    /// Much cheaper with thousands of shards, and looks like particles.
+    Points
+}
+
+impl RenderStyle {
+    pub fn next(self) -> RenderStyle {
+        match self {
+            RenderStyle::Solid => RenderStyle::Points,
+            RenderStyle::Points => RenderStyle::Solid
+        }
+    }
+}
+
+/// Something that happened during a step, for sound or other effects to react to.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    /// A subcube split into shards. `subcube_length` is how big it was
+    /// before splitting, so effects can scale with it.
+    SubcubeExploded { index: usize, pos: Vector3<f32>, subcube_length: f32 }
 }
 
 /// One-off data derived from GameState and used by the renderer.
@@ -22,66 +278,597 @@ pub struct GameState {
 /// use by the renderer.
 pub struct GameStepResult {
     pub projection_view: cgmath::Matrix4<f32>,
-    pub selected_subcube: Option<usize>
+    pub selected_subcube: Option<usize>,
+    /// The `Cube::generation` that `selected_subcube` indexes into
+    pub generation: u64,
+    /// The world-space ray under the pointer that picked `selected_subcube`.
+    /// Only set if `GameState::debug_picking` is on.
+    pub debug_ray: Option<Ray3<f32>>,
+    /// Where `debug_ray` hits `selected_subcube`
+    pub debug_hit: Option<Point3<f32>>,
+    pub events: Vec<GameEvent>
 }
 
-#[derive(Default)]
+impl GameStepResult {
+    /// Describe `debug_ray` and what it hit as a JSON object, e.g.
+    /// `{"origin":[0,0,5],"direction":[0,0,-1],"hit":3,"hit_point":[0,0,0.5]}`.
+    /// Returns None if there's no debug ray.
+    pub fn debug_ray_json(&self) -> Option<String> {
+        let ray = self.debug_ray?;
+        let (o, d) = (ray.origin, ray.direction);
+        let hit = match self.selected_subcube {
+            Some(index) => index.to_string(),
+            None => "null".to_string()
+        };
+        let hit_point = match self.debug_hit {
+            Some(p) => format!("[{},{},{}]", p.x, p.y, p.z),
+            None => "null".to_string()
+        };
+
+        Some(format!(
+            "{{\"origin\":[{},{},{}],\"direction\":[{},{},{}],\"hit\":{},\"hit_point\":{}}}",
+            o.x, o.y, o.z, d.x, d.y, d.z, hit, hit_point
+        ))
+    }
+}
+
+/// Everything needed to bring back a scene: the subcubes, the camera and
+/// how it's drawn. The cube's tuning, such as `max_speed`, isn't included.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameStateSave {
+    pub subcubes: Vec<cube::Subcube>,
+    /// What resetting returns to
+    pub initial_subcubes: Vec<cube::Subcube>,
+    pub orientation: cgmath::Quaternion<f32>,
+    pub zoom: f32,
+    /// The point the view orbits around
+    pub pivot: Vector3<f32>,
+    pub fovy: f32,
+    pub outline_mode: OutlineMode,
+    pub shadow_mode: ShadowMode,
+    pub shading_mode: ShadingMode,
+    pub render_style: RenderStyle,
+    pub cull_faces: bool,
+    pub show_ghost: bool,
+    pub seed: Option<u64>
+}
+
+/// A snapshot of a single subcube's transform and motion.
+#[derive(Copy, Clone, Debug)]
+pub struct SubcubeInfo {
+    pub index: usize,
+    pub pos: Vector3<f32>,
+    pub orientation: cgmath::Quaternion<f32>,
+    /// The same rotation as `orientation`, as Euler angles
+    pub euler: cgmath::Euler<cgmath::Rad<f32>>,
+    pub subcube_length: f32,
+    pub velocity: Vector3<f32>
+}
+
+#[derive(Default, Clone)]
 pub struct GameInput {
     pub hurl_all: bool,
     pub explode_subcube: bool,
+    /// Split the hovered subcube without hurling the shards
+    pub crack_subcube: bool,
     pub rearrange: bool,
+    /// Stop every subcube where it is
+    pub freeze: bool,
     pub reset: bool,
-    pub toggle_show_outlines: bool,
+    pub cycle_outline_mode: bool,
+    pub toggle_cull_faces: bool,
+    pub toggle_orbit_inertia: bool,
+    pub toggle_show_ghost: bool,
+    pub cycle_shadow_mode: bool,
+    pub toggle_debug_picking: bool,
+    pub toggle_show_indices: bool,
+    /// Switch to the next `PaletteMode`, or off after the last one
+    pub cycle_palette_mode: bool,
+    pub cycle_shading_mode: bool,
+    /// Switch between drawing subcubes as solids and as points
+    pub cycle_render_style: bool,
+    /// Ease to the other `ProjectionMode`
+    pub toggle_projection: bool,
+    /// Switch what exploded subcubes split into, cubes or spheres
+    pub toggle_explode_shape: bool,
+    /// Switch between keeping the subcubes in `Cube::default_bounds` and letting them fly
+    pub toggle_bounds: bool,
+    /// Orbit the view around the hovered subcube
+    pub focus_subcube: bool,
+    /// Orbit the view around the origin
+    pub reset_pivot: bool,
+    /// Ease the view back to how it started, leaving the cube alone
+    pub reset_camera: bool,
+    /// Show the reticle at the center of the screen, or hide it
+    pub toggle_reticle: bool,
+    /// Pull nearby subcubes toward the pointer, for as long as it's set
+    pub magnet: bool,
+    /// Move `GameState::selected` on to the next subcube
+    pub select_next: bool,
+    /// Move `GameState::selected` back to the previous subcube
+    pub select_prev: bool,
+    /// Explode `GameState::selected`, wherever the pointer is
+    pub explode_selected: bool,
+    /// How far to move the reticle, in pointer coordinates
+    pub move_reticle: (f32, f32),
     /// The pointer coordinates range from -1.0 to +1.0.
     /// e.g. (0.0, 0.0) is the center, (1.0, 1.0) is the top-right.
     pub pointer: Option<(f32, f32)>,
     pub rotate_view: (f32, f32),
-    pub zoom_view_change: f32
+    /// The view was let go after rotating it
+    pub release_view: bool,
+    pub zoom_view_change: f32,
+    /// Change to the vertical field of view, in degrees.
+    pub fov_change: f32
 }
 
 impl GameInput {
     pub fn new() -> GameInput { std::default::Default::default() }
 }
 
+impl Default for GameState {
+    fn default() -> GameState {
+        GameState::new()
+    }
+}
+
 impl GameState {
     pub fn new() -> GameState {
         GameState {
             cube: Cube::new(),
-            show_outlines: true,
+            outline_mode: OutlineMode::Geometry,
+            cull_faces: true,
+            orbit_inertia: true,
+            show_ghost: false,
+            shadow_mode: ShadowMode::None,
+            debug_picking: false,
+            show_indices: false,
+            palette: Palette::rubiks(),
+            palette_mode: None,
+            inner_color: Some(Vector3::new(0.3, 0.3, 0.35)),
+            shading_mode: ShadingMode::Normal,
+            render_style: RenderStyle::Solid,
+            edge_darkening: 0.0,
+            trail_fade: 1.0,
+            srgb_output: false,
+            texture_path: None,
+            texture_mipmaps: true,
+            idle_spin: None,
+            idle_timeout: 5.0,
+            idle_time: 0.0,
+            drill_interval: 0.15,
+            drill_time: None,
+            explode_cooldown: 0.05,
+            explode_subdivisions: 2,
+            explode_fuse: None,
+            blast_away_from_viewer: None,
+            magnet: None,
+            magnet_strength: 12.0,
+            time_since_explosion: f32::INFINITY,
+            fovy: 45.0,
+            projection_mode: ProjectionMode::Perspective,
+            ortho_blend: 0.0,
+            near: 0.1,
+            far: 100.0,
             orientation: physics::QuaternionMotion::new(
-                Rotation::look_at(Vector3::new(0.5, 0.25, 0.5), Vector3::new(0.0, 1.0, 0.0)),
+                initial_orientation(),
                 Vector3::new(0.0, 0.2, 0.0),
                 0.5
             ),
-            zoom: physics::ScalarMotion::new(0.5, 0.2, 0.9)
+            zoom: physics::ScalarMotion::new(INITIAL_ZOOM, 0.2, 0.9),
+            pivot: physics::VectorApproach::new(Vector3::zero(), 0.99),
+            reticle: None,
+            zoom_to_pointer: true,
+            pick_tolerance: 0.0,
+            zoom_anchor: None,
+            resetting_camera: false,
+            seed: None,
+            up_axis: Vector3::unit_y(),
+            sim_logger: None,
+            selected: None,
+            pick_cache: None,
+            queued_actions: Vec::new(),
+            palette_applied: None
+        }
+    }
+
+    /// The subcube picked out with the keyboard, if any.
+    /// Unlike `GameStepResult::selected_subcube`, it stays put when the pointer moves.
+    /// Resetting or restoring the cube's subcubes clears it.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+            .filter(|&(index, generation)| generation == self.cube.generation() && index < self.cube.subcubes.len())
+            .map(|(index, _)| index)
+    }
+
+    /// `Cube::retain_subcubes`, keeping the selection on the same subcube, or
+    /// clearing it if that one's removed.
+    pub fn retain_subcubes<F: FnMut(&cube::Subcube) -> bool>(&mut self, f: F) -> Vec<Option<usize>> {
+        let selected = self.selected();
+        let map = self.cube.retain_subcubes(f);
+        let generation = self.cube.generation();
+        self.selected = selected.and_then(|index| map.get(index).copied().flatten()).map(|index| (index, generation));
+        map
+    }
+
+    /// Select the subcube after the selected one, in the order of where they
+    /// belong in the cube. Wraps around to the first after the last, and
+    /// selects the first if nothing was selected.
+    pub fn select_next(&mut self) {
+        self.select_by(1);
+    }
+
+    /// Select the subcube before the selected one. The reverse of `select_next`.
+    pub fn select_prev(&mut self) {
+        self.select_by(-1);
+    }
+
+    fn select_by(&mut self, offset: isize) {
+        // Where subcubes belong doesn't change as they fly around, so the
+        // order only changes when they split
+        let subcubes = &self.cube.subcubes;
+        let mut order: Vec<usize> = (0..subcubes.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (subcubes[a].segment, subcubes[b].segment);
+            a.z.total_cmp(&b.z).then(a.y.total_cmp(&b.y)).then(a.x.total_cmp(&b.x))
+        });
+        if order.is_empty() {
+            self.selected = None;
+            return;
+        }
+
+        let len = order.len() as isize;
+        let next = match self.selected().and_then(|index| order.iter().position(|&i| i == index)) {
+            Some(at) => (at as isize + offset).rem_euclid(len),
+            None if offset < 0 => len - 1,
+            None => 0
+        };
+        self.selected = Some((order[next as usize], self.cube.generation()));
+    }
+
+    /// The subcube the ray hits first, and where. Reuses the last pick while
+    /// the ray and cube are the same as they were, since holding still over
+    /// a settled cube would otherwise test every subcube every step for the
+    /// same answer.
+    fn pick(&mut self, ray: &Ray3<f32>) -> Option<(usize, Point3<f32>)> {
+        let key = (ray.origin, ray.direction, self.cube.version(), self.cube.pick_unpickable, self.pick_tolerance);
+        match self.pick_cache {
+            Some(c) if (c.origin, c.direction, c.version, c.pick_unpickable, c.tolerance) == key => c.hit,
+            _ => {
+                let hit = self.cube.get_subcube_hit_from_ray(ray).or_else(|| match self.pick_tolerance {
+                    tolerance if tolerance > 0.0 => self.cube.get_subcube_near_ray(ray, tolerance),
+                    _ => None
+                });
+                let (origin, direction, version, pick_unpickable, tolerance) = key;
+                self.pick_cache = Some(PickCache { origin, direction, version, pick_unpickable, tolerance, hit });
+                hit
+            }
+        }
+    }
+
+    /// Explode the subcube now, or arm it if `explode_fuse` is set.
+    fn explode(&mut self, index: usize, events: &mut Vec<GameEvent>) {
+        if let Some(delay) = self.explode_fuse {
+            let min_length = self.cube.min_subcube_length();
+            self.cube.arm_explosion(index, delay, 4.0, self.explode_subdivisions, min_length);
+        } else {
+            let (pos, subcube_length) = (self.cube.subcubes[index].pos, self.cube.subcubes[index].subcube_length);
+            let count = self.cube.subcubes.len();
+            self.cube.explode_subcube(index, 4.0, self.explode_subdivisions);
+            // Subcubes under the minimum length don't split
+            if self.cube.subcubes.len() != count {
+                events.push(GameEvent::SubcubeExploded { index, pos, subcube_length });
+            }
+        }
+        self.time_since_explosion = 0.0;
+    }
+
+    /// Do `action` on the next step, as if it were pressed in that step's input.
+    pub fn apply_action(&mut self, action: Action) {
+        self.queued_actions.push(action);
+    }
+
+    pub fn up_axis(&self) -> Vector3<f32> {
+        self.up_axis
+    }
+
+    /// Set which way is up, e.g. `Vector3::unit_z()` for a Z-up scene.
+    /// The camera is set upright about it, as it starts and resets, and
+    /// settling subcubes and the renderer's floor follow it too.
+    ///
+    /// # Panics
+    /// Panics if `up` is zero or not finite.
+    pub fn set_up_axis(&mut self, up: Vector3<f32>) {
+        assert!(up.magnitude2() > 0.0 && up.magnitude2().is_finite());
+        self.up_axis = up.normalize();
+        self.cube.up_axis = self.up_axis;
+        self.orientation.quaternion = self.upright_orientation();
+        self.orientation.angular_momentum = Vector3::zero();
+    }
+
+    /// The rotation from a Y-up scene to this one, turning +Y onto `up_axis`.
+    /// Anything laid out with Y up, such as a light, fits once rotated by it.
+    pub fn up_rotation(&self) -> cgmath::Quaternion<f32> {
+        cgmath::Quaternion::from_arc(Vector3::unit_y(), self.up_axis, Some(Vector3::unit_x()))
+    }
+
+    /// Ease the pivot the view orbits around over to `point`, like
+    /// `GameInput::focus_subcube` does to the subcube's position.
+    pub fn focus_on(&mut self, point: Vector3<f32>) {
+        self.pivot.target = point;
+        self.zoom_anchor = None;
+    }
+
+    /// Where the camera starts, and resets to
+    fn upright_orientation(&self) -> cgmath::Quaternion<f32> {
+        // Turn the scene Y-up, then look at it as usual
+        initial_orientation() * self.up_rotation().conjugate()
+    }
+
+    /// Ease the view back to the orientation, zoom and pivot it started with.
+    /// The cube is left alone. Rotating or zooming the view stops the reset.
+    pub fn reset_camera(&mut self) {
+        self.orientation.angular_momentum = Vector3::zero();
+        self.zoom.change = 0.0;
+        self.pivot.target = Vector3::zero();
+        self.zoom_anchor = None;
+        self.idle_time = 0.0;
+        self.resetting_camera = true;
+    }
+
+    /// Capture the scene, to `load` later.
+    pub fn save(&self) -> GameStateSave {
+        GameStateSave {
+            subcubes: self.cube.subcubes.clone(),
+            initial_subcubes: self.cube.initial_subcubes().to_vec(),
+            orientation: self.orientation.quaternion,
+            zoom: self.zoom.scalar,
+            pivot: self.pivot.vector,
+            fovy: self.fovy,
+            outline_mode: self.outline_mode,
+            shadow_mode: self.shadow_mode,
+            shading_mode: self.shading_mode,
+            render_style: self.render_style,
+            cull_faces: self.cull_faces,
+            show_ghost: self.show_ghost,
+            seed: self.seed
+        }
+    }
+
+    /// Bring back a scene from `save`. The camera comes back at rest.
+    ///
+    /// If the save has a seed, the random numbers are reseeded with it, so
+    /// what follows a load is repeatable. It won't match what followed the
+    /// save unless that was right after seeding.
+    pub fn load(&mut self, save: GameStateSave) {
+        self.cube.restore(save.subcubes, save.initial_subcubes);
+        self.orientation.quaternion = save.orientation;
+        self.orientation.angular_momentum = Vector3::zero();
+        self.zoom.scalar = save.zoom;
+        self.zoom.change = 0.0;
+        self.pivot.vector = save.pivot;
+        self.pivot.target = save.pivot;
+        self.zoom_anchor = None;
+        self.resetting_camera = false;
+        self.selected = None;
+        self.fovy = save.fovy;
+        self.outline_mode = save.outline_mode;
+        self.shadow_mode = save.shadow_mode;
+        self.shading_mode = save.shading_mode;
+        self.render_style = save.render_style;
+        self.cull_faces = save.cull_faces;
+        self.show_ghost = save.show_ghost;
+        self.seed = save.seed;
+        if let Some(seed) = save.seed {
+            quad_rand::srand(seed);
+        }
+    }
+
+    /// Start with the cube already split `n` ways on each axis.
+    /// See `Cube::new_subdivided`.
+    pub fn new_with_subdivision(n: u32) -> GameState {
+        GameState {
+            cube: Cube::new_subdivided(n),
+            ..GameState::new()
         }
     }
 
     pub fn steps_per_second() -> u32 { 60 }
 
+    /// How much the view's spin slows down every second, from 0 to 1.
+    pub fn orbit_decay(&self) -> f32 {
+        self.orientation.decay
+    }
+
+    /// Set how much the view's spin slows down every second.
+    /// 0 never slows down, and 1 stops completely after a second.
+    /// The value is clamped to that range.
+    pub fn set_orbit_decay(&mut self, decay: f32) {
+        self.orientation.decay = decay.clamp(0.0, 1.0);
+    }
+
+    /// How much zooming slows down every second, from 0 to 1.
+    pub fn zoom_decay(&self) -> f32 {
+        self.zoom.decay
+    }
+
+    /// Set how much zooming slows down every second.
+    /// 0 never slows down, and 1 stops completely after a second.
+    /// The value is clamped to that range.
+    pub fn set_zoom_decay(&mut self, decay: f32) {
+        self.zoom.decay = decay.clamp(0.0, 1.0);
+    }
+
     pub fn step(&mut self, viewport: (i32,i32), input: &GameInput) -> GameStepResult {
         let frac = 1.0 / GameState::steps_per_second() as f32;
 
+        let mut input = input.clone();
+        for action in self.queued_actions.drain(..) {
+            *action.flag(&mut input) = true;
+        }
+        let input = &input;
+        let mut events = Vec::new();
+
+        if let Some(strength) = self.blast_away_from_viewer {
+            self.cube.hurl_bias = self.camera_forward() * strength;
+        }
+
         if input.hurl_all {
             self.cube.try_hurl_all(4.0);
+        } else if input.freeze {
+            self.cube.try_freeze();
         } else if input.rearrange {
             self.cube.try_rearrange();
         } else if input.reset {
             self.cube.try_reset();
         }
 
+        if input.reset_camera {
+            self.reset_camera();
+        }
+
+        if input.toggle_reticle {
+            self.reticle = match self.reticle {
+                Some(_) => None,
+                None => Some((0.0, 0.0))
+            };
+        }
+        if let Some((x, y)) = self.reticle.as_mut() {
+            let (dx, dy) = input.move_reticle;
+            *x = (*x + dx).clamp(-1.0, 1.0);
+            *y = (*y + dy).clamp(-1.0, 1.0);
+        }
+        let pointer = self.reticle.or(input.pointer);
+
         let projection_view = self.solve_projection_view(viewport);
 
-        let selected_subcube = self.solve_selected_subcube(projection_view, input.pointer);
+        let pointer_ray = self.solve_pointer_ray(projection_view, pointer);
+        let hit = pointer_ray.as_ref().and_then(|ray| self.pick(ray));
+        let selected_subcube = hit.map(|(index, _)| index);
+        let generation = self.cube.generation();
 
-        if input.explode_subcube {
+        if input.focus_subcube {
             if let Some(s) = selected_subcube {
-                self.cube.explode_subcube_if_at_least(s, 4.0, 2, 1.0/16.0);
+                self.focus_on(self.cube.subcubes[s].pos);
             }
+        } else if input.reset_pivot {
+            self.focus_on(Vector3::zero());
+        }
+
+        // A fresh press explodes as soon as the cooldown allows and there's
+        // something under the pointer; holding it explodes again every
+        // drill_interval at whatever is under the pointer by then
+        let wants_explosion = input.explode_subcube && self.drill_time.is_none_or(|t| t >= self.drill_interval);
+        self.drill_time = match self.drill_time {
+            _ if !input.explode_subcube => None,
+            t => t.map(|t| t + frac)
+        };
+        self.time_since_explosion += frac;
+
+        if wants_explosion && self.time_since_explosion >= self.explode_cooldown {
+            if let Some(s) = selected_subcube {
+                self.explode(s, &mut events);
+                self.drill_time = Some(0.0);
+            }
+        }
+
+        // The pointer is taken to be as far away as the pivot, so the magnet
+        // can be held out in the empty space around the cube too
+        self.magnet = match pointer_ray {
+            Some(ref ray) if input.magnet => self.point_at_pivot_depth(ray).map(|p| (p, self.magnet_strength)),
+            _ => None
+        };
+        self.cube.magnet = self.magnet;
+
+        if input.select_next {
+            self.select_next();
+        } else if input.select_prev {
+            self.select_prev();
+        }
+        // The selected subcube keeps its index when it splits, so it stays
+        // selected as the first of its shards
+        if input.explode_selected && self.time_since_explosion >= self.explode_cooldown {
+            if let Some(s) = self.selected() {
+                self.explode(s, &mut events);
+            }
+        }
+
+        if input.crack_subcube {
+            if let Some(s) = selected_subcube {
+                let min_length = self.cube.min_subcube_length();
+                self.cube.crack_subcube(s, self.explode_subdivisions, min_length);
+            }
+        }
+
+        if input.cycle_outline_mode {
+            self.outline_mode = self.outline_mode.next();
+        }
+
+        if input.toggle_cull_faces {
+            self.cull_faces = !self.cull_faces;
+        }
+
+        if input.toggle_orbit_inertia {
+            self.orbit_inertia = !self.orbit_inertia;
+        }
+
+        if input.toggle_show_ghost {
+            self.show_ghost = !self.show_ghost;
+        }
+
+        if input.cycle_shadow_mode {
+            self.shadow_mode = self.shadow_mode.next();
+        }
+
+        if input.toggle_debug_picking {
+            self.debug_picking = !self.debug_picking;
+        }
+
+        if input.toggle_show_indices {
+            self.show_indices = !self.show_indices;
+        }
+
+        if input.cycle_palette_mode {
+            self.palette_mode = match self.palette_mode {
+                None => Some(PaletteMode::ByFace),
+                Some(mode) => mode.next()
+            };
+            match self.palette_mode {
+                Some(mode) => self.cube.apply_palette(&self.palette, mode),
+                None => self.cube.for_each_subcube_mut(|s| s.set_color(None))
+            }
+        }
+        let palette_key = self.palette_mode.map(|mode| (mode, self.cube.generation(), self.cube.subcubes.len()));
+        if let Some(mode) = self.palette_mode.filter(|_| self.palette_applied != palette_key) {
+            // Resets bring back uncolored subcubes, and shards inherit their
+            // parent's color, which is the wrong one by depth
+            let palette = &self.palette;
+            self.cube.for_each_subcube_mut(|s| {
+                if s.color().is_none() || mode == PaletteMode::ByDepth {
+                    s.apply_palette(palette, mode);
+                }
+            });
+            self.palette_applied = palette_key;
+        }
+
+        if input.toggle_explode_shape {
+            self.cube.explode_shape = self.cube.explode_shape.next();
+        }
+
+        if input.toggle_bounds {
+            self.cube.bounds = match self.cube.bounds {
+                Some(_) => None,
+                None => Some(Cube::default_bounds())
+            };
         }
 
-        if input.toggle_show_outlines {
-            self.show_outlines = !self.show_outlines;
+        if input.cycle_shading_mode {
+            self.shading_mode = self.shading_mode.next();
+        }
+
+        if input.cycle_render_style {
+            self.render_style = self.render_style.next();
         }
 
         {
@@ -89,24 +876,107 @@ impl GameState {
             if (x,y) != (0.0,0.0) {
                 let ang = Vector3::new(-y, x, 0.0) * 32.0;
                 self.orientation.angular_momentum = ang;
+                self.idle_time = 0.0;
+                self.resetting_camera = false;
+            } else {
+                self.idle_time += frac;
+            }
+        }
+        if input.release_view && !self.orbit_inertia {
+            self.orientation.angular_momentum = Vector3::zero();
+        }
+        if let Some(spin) = self.idle_spin {
+            if self.idle_time >= self.idle_timeout {
+                // Ease into the spin rather than snapping to it
+                let ang = self.orientation.angular_momentum;
+                self.orientation.angular_momentum = ang.lerp(spin, frac);
             }
         }
         self.zoom.change -= input.zoom_view_change * 1.0/2.0;
+        if input.zoom_view_change != 0.0 {
+            self.resetting_camera = false;
+            // Over nothing, zoom toward the pivot as usual
+            self.zoom_anchor = hit.filter(|_| self.zoom_to_pointer).map(|(_, point)| point.to_vec());
+        }
+        if self.resetting_camera {
+            let amount = physics::approach_amount(CAMERA_RESET_RATE, frac);
+
+            // q and -q are the same rotation; take the short way around
+            let mut target = self.upright_orientation();
+            if self.orientation.quaternion.dot(target) < 0.0 {
+                target = -target;
+            }
+            self.orientation.quaternion = self.orientation.quaternion.nlerp(target, amount);
+            self.zoom.scalar += (INITIAL_ZOOM - self.zoom.scalar) * amount;
+
+            // Close enough; let the idle spin take over again
+            if self.orientation.quaternion.dot(target) > 0.99999 && (INITIAL_ZOOM - self.zoom.scalar).abs() < 0.001 {
+                self.resetting_camera = false;
+            }
+        }
+        self.fovy = (self.fovy + input.fov_change).clamp(10.0, 120.0);
+
+        if input.toggle_projection {
+            self.projection_mode = self.projection_mode.next();
+        }
+        let target = match self.projection_mode {
+            ProjectionMode::Perspective => 0.0,
+            ProjectionMode::Orthographic => 1.0
+        };
+        let blend_change = frac / PROJECTION_TRANSITION_TIME;
+        self.ortho_blend = (self.ortho_blend + (target - self.ortho_blend).clamp(-blend_change, blend_change)).clamp(0.0, 1.0);
 
         self.orientation.step(frac);
+        let distance = self.camera_distance();
         self.zoom.step(frac);
-        self.cube.step(frac);
+        if let Some(anchor) = self.zoom_anchor {
+            // Scaling the camera and pivot about the anchor keeps it under the pointer
+            let shift = (anchor - self.pivot.vector) * (1.0 - self.camera_distance() / distance);
+            self.pivot.vector += shift;
+            self.pivot.target += shift;
+        }
+        self.pivot.step(frac);
+        for detonation in self.cube.step(frac) {
+            if detonation.split {
+                let Detonation { index, pos, subcube_length, .. } = detonation;
+                events.push(GameEvent::SubcubeExploded { index, pos, subcube_length });
+            }
+        }
+        if let Some(logger) = self.sim_logger.as_mut() {
+            logger.log(&self.cube);
+        }
 
         GameStepResult {
-            projection_view: projection_view,
-            selected_subcube: selected_subcube
+            projection_view,
+            selected_subcube,
+            generation,
+            debug_ray: pointer_ray.filter(|_| self.debug_picking),
+            debug_hit: hit.map(|(_, point)| point).filter(|_| self.debug_picking),
+            events
         }
     }
 
-    fn solve_selected_subcube(&self, projection_view: cgmath::Matrix4<f32>, pointer: Option<(f32, f32)>) -> Option<usize> {
-        let Some((x, y)) = pointer else {
-            return None;
-        };
+    /// Get the transform of the subcube that was selected during the step.
+    /// Returns None if nothing was selected, or if the subcube no longer
+    /// exists, such as after a reset.
+    pub fn selected_subcube_transform(&self, result: &GameStepResult) -> Option<SubcubeInfo> {
+        let index = result.selected_subcube.filter(|_| result.generation == self.cube.generation())?;
+        let subcube = self.cube.subcubes.get(index)?;
+
+        Some(SubcubeInfo {
+            index,
+            pos: subcube.pos,
+            orientation: subcube.orientation,
+            euler: subcube.orientation.into(),
+            subcube_length: subcube.subcube_length,
+            velocity: subcube.velocity()
+        })
+    }
+
+    /// Get the world-space ray that starts at the near plane under the pointer.
+    /// Returns None if there's no pointer.
+    pub fn solve_pointer_ray(&self, projection_view: cgmath::Matrix4<f32>, pointer: Option<(f32, f32)>) -> Option<Ray3<f32>> {
+        let (x, y) = pointer?;
 
         // From NDC to world coordinates
         let post_project_v1 = Vector4::new(x, y, -1.0, 1.0);
@@ -118,26 +988,574 @@ impl GameState {
 
         let direction = (pre_project_p2 - pre_project_p1).normalize();
 
-        let mouse_ray = Ray::new(pre_project_p1, direction);
+        Some(Ray::new(pre_project_p1, direction))
+    }
+
+    /// Where the ray crosses the plane through the pivot that faces the
+    /// camera, or None if it's pointing away from it.
+    pub fn point_at_pivot_depth(&self, ray: &Ray3<f32>) -> Option<Vector3<f32>> {
+        let forward = self.camera_forward();
+        let along = ray.direction.dot(forward);
+        if along <= 0.0 {
+            return None;
+        }
+        let t = (self.pivot.vector - ray.origin.to_vec()).dot(forward) / along;
+        (t >= 0.0).then(|| ray.origin.to_vec() + ray.direction * t)
+    }
+
+    /// The near and far clipping distances the projection actually uses.
+    /// They're kept apart, or the projection (and its inverse used for
+    /// picking) degenerates.
+    pub fn clip_planes(&self) -> (f32, f32) {
+        let near = self.near.max(0.001);
+        let far = self.far.max(near * 1.01);
+        (near, far)
+    }
+
+    /// The near and far clipping distances of the projection as it's drawn.
+    /// While easing into orthographic they move back with the camera, so
+    /// they cut the scene in the same places as `clip_planes`.
+    pub fn projection_clip_planes(&self) -> (f32, f32) {
+        let (near, far) = self.clip_planes();
+        match self.dolly_tan_half_fovy() {
+            Some(dolly_tan) => {
+                let extra = self.framed_half_height() / dolly_tan - self.camera_distance();
+                (near + extra, far + extra)
+            },
+            None => (near, far)
+        }
+    }
+
+    /// Whether the projection is all the way to orthographic, where depth
+    /// is linear in distance rather than in its reciprocal.
+    pub fn is_orthographic(&self) -> bool {
+        self.dolly_tan_half_fovy().is_none()
+    }
+
+    /// The direction the camera looks in, in world space.
+    /// Useful as `Cube::hurl_bias`, to blast debris away from the viewer.
+    pub fn camera_forward(&self) -> Vector3<f32> {
+        // The view rotates the world, so undo it on the view's -Z
+        self.orientation.quaternion.conjugate() * -Vector3::unit_z()
+    }
+
+    /// The direction that's up on the screen, in world space.
+    pub fn camera_up(&self) -> Vector3<f32> {
+        self.orientation.quaternion.conjugate() * Vector3::unit_y()
+    }
 
-        self.cube.get_subcube_from_ray(&mouse_ray).map(|(index, _)| index)
+    /// The view's rotation as a matrix. Its columns are always orthonormal.
+    pub fn orientation_matrix(&self) -> cgmath::Matrix3<f32> {
+        self.orientation.quaternion.normalize().into()
     }
 
-    fn solve_projection_view(&self, viewport: (i32,i32)) -> cgmath::Matrix4<f32> {
-        let viewport_aspect = match viewport {
-            (width, height) => width as f32 / height as f32
+    /// How far the camera is from the pivot.
+    fn camera_distance(&self) -> f32 {
+        1.0 + 5.0f32.powf(self.zoom.scalar)
+    }
+
+    /// Half the height the view frames at the pivot.
+    fn framed_half_height(&self) -> f32 {
+        self.camera_distance() * (self.fovy.to_radians() / 2.0).tan()
+    }
+
+    /// The tangent of half the vertical field of view, narrowed part way to
+    /// orthographic, or None once it's all the way there.
+    fn dolly_tan_half_fovy(&self) -> Option<f32> {
+        // Ease in and out of the dolly
+        let t = self.ortho_blend;
+        let t = t * t * (3.0 - 2.0 * t);
+        (t < 1.0).then(|| (self.fovy.to_radians() / 2.0).tan() * (1.0 - t))
+    }
+
+    /// How many half-viewport-heights a world unit spans on screen, at a
+    /// clip-space w of 1. Divide by w for the size at some other depth. In
+    /// orthographic projection w is always 1.
+    pub fn screen_scale(&self) -> f32 {
+        match self.dolly_tan_half_fovy() {
+            Some(tan) => 1.0 / tan,
+            None => 1.0 / self.framed_half_height()
+        }
+    }
+
+    /// The projection-view matrix for the current camera, drawn to a
+    /// viewport of this size in pixels.
+    ///
+    /// Part way between perspective and orthographic, the camera backs away
+    /// while the field of view narrows, keeping the pivot framed the same.
+    /// So the scene flattens smoothly rather than jumping, and picking with
+    /// the matrix always matches what's drawn.
+    pub fn solve_projection_view(&self, viewport: (i32,i32)) -> cgmath::Matrix4<f32> {
+        let (width, height) = viewport;
+        let viewport_aspect = width as f32 / height as f32;
+        let (near, far) = self.projection_clip_planes();
+        let distance = self.camera_distance();
+        let half_height = self.framed_half_height();
+
+        let (projection, distance): (cgmath::Matrix4<f32>, f32) = match self.dolly_tan_half_fovy() {
+            None => {
+                let half_width = half_height * viewport_aspect;
+                (cgmath::ortho(-half_width, half_width, -half_height, half_height, near, far), distance)
+            },
+            Some(dolly_tan) => {
+                let projection = cgmath::PerspectiveFov {
+                    fovy: cgmath::Rad(2.0 * dolly_tan.atan()),
+                    aspect: viewport_aspect,
+                    near,
+                    far
+                }.into();
+                (projection, half_height / dolly_tan)
+            }
         };
-        let projection: cgmath::Matrix4<f32> = cgmath::PerspectiveFov {
-            fovy: cgmath::Deg(45.0).into(),
-            aspect: viewport_aspect,
-            near: 0.1,
-            far: 100.0
-        }.into();
 
         let view = cgmath::Matrix4::identity()
-            .translate(0.0, 0.0, -1.0 + -(5.0f32.powf(self.zoom.scalar)))
-            .quaternion(&self.orientation.quaternion);
+            .translate(0.0, 0.0, -distance)
+            .quaternion(&self.orientation.quaternion)
+            .translate_v(&-self.pivot.vector);
 
         projection * view
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applied_actions_happen_on_the_next_step_only() {
+        let mut state = GameState::new();
+        state.apply_action(Action::CycleShadowMode);
+        state.apply_action(Action::ToggleShowIndices);
+        assert_eq!(state.shadow_mode, ShadowMode::None);
+
+        state.step((800, 600), &GameInput::new());
+        assert_eq!(state.shadow_mode, ShadowMode::None.next());
+        assert!(state.show_indices);
+
+        state.step((800, 600), &GameInput::new());
+        assert_eq!(state.shadow_mode, ShadowMode::None.next());
+        assert!(state.show_indices);
+    }
+
+    #[test]
+    fn projection_eases_to_orthographic_keeping_the_pivot_framed() {
+        let mut state = GameState::new();
+        state.idle_spin = None;
+        state.orientation.angular_momentum = Vector3::zero();
+        let viewport = (800, 600);
+        // How far off being framed as in perspective a point is, on the plane
+        // through the pivot facing the camera
+        let misframed = |state: &GameState| {
+            let p = state.solve_projection_view(viewport) * (state.camera_up() * 0.5).extend(1.0);
+            (p.y / p.w - 0.5 / state.framed_half_height()).abs()
+        };
+        assert!(misframed(&state) < 1e-4);
+
+        state.step(viewport, &GameInput { toggle_projection: true, ..GameInput::new() });
+        for _ in 0..GameState::steps_per_second() / 10 {
+            state.step(viewport, &GameInput::new());
+        }
+        assert!(state.ortho_blend > 0.0 && state.ortho_blend < 1.0);
+        assert!(misframed(&state) < 1e-4);
+        // Picking goes through the projection as it's drawn, mid-transition too
+        let result = state.step(viewport, &GameInput { pointer: Some((0.0, 0.0)), ..GameInput::new() });
+        assert_eq!(result.selected_subcube, Some(0));
+
+        for _ in 0..GameState::steps_per_second() {
+            state.step(viewport, &GameInput::new());
+        }
+        assert_eq!(state.ortho_blend, 1.0);
+        assert!(misframed(&state) < 1e-4);
+        let result = state.step(viewport, &GameInput { pointer: Some((0.0, 0.0)), ..GameInput::new() });
+        assert_eq!(result.selected_subcube, Some(0));
+    }
+
+    #[test]
+    fn depth_turns_back_into_distance_through_the_transition() {
+        let mut state = GameState::new();
+        let viewport = (800, 600);
+        // What the screen-space outlines work out the pivot's distance to be from its depth
+        let pivot_distance = |state: &GameState| {
+            let p = state.solve_projection_view(viewport) * state.pivot.vector.extend(1.0);
+            let z = p.z / p.w;
+            let (near, far) = state.projection_clip_planes();
+            let linear = match state.is_orthographic() {
+                true => near + (z * 0.5 + 0.5) * (far - near),
+                false => 2.0 * near * far / (far + near - z * (far - near))
+            };
+            // In perspective w is the distance; orthographic keeps the camera where it was
+            let distance = if state.is_orthographic() { state.camera_distance() } else { p.w };
+            (linear - distance).abs() / distance
+        };
+        assert!(pivot_distance(&state) < 1e-3);
+
+        state.step(viewport, &GameInput { toggle_projection: true, ..GameInput::new() });
+        for _ in 0..GameState::steps_per_second() / 10 {
+            state.step(viewport, &GameInput::new());
+        }
+        assert!(!state.is_orthographic());
+        assert!(pivot_distance(&state) < 1e-3);
+
+        for _ in 0..GameState::steps_per_second() {
+            state.step(viewport, &GameInput::new());
+        }
+        assert!(state.is_orthographic());
+        assert!(pivot_distance(&state) < 1e-3);
+    }
+
+    #[test]
+    fn orientation_stays_orthonormal_over_long_runs() {
+        let mut state = GameState::new();
+        state.idle_spin = None;
+        for i in 0..20_000 {
+            let t = i as f32 * 0.01;
+            let input = GameInput { rotate_view: (t.sin() * 0.01, t.cos() * 0.007), ..GameInput::new() };
+            state.step((800, 600), &input);
+        }
+
+        let m = state.orientation_matrix();
+        for (i, a) in [m.x, m.y, m.z].iter().enumerate() {
+            for (j, b) in [m.x, m.y, m.z].iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((a.dot(*b) - expected).abs() < 1e-5, "columns {} and {} aren't orthonormal", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn explosions_report_the_size_they_split_from() {
+        let mut state = GameState::new();
+        let explode = GameInput { explode_subcube: true, pointer: Some((0.0, 0.0)), ..GameInput::new() };
+
+        let result = state.step((800, 600), &explode);
+        match result.events[..] {
+            [GameEvent::SubcubeExploded { index: 0, subcube_length, .. }] => assert_eq!(subcube_length, 1.0),
+            ref events => panic!("Expected one explosion, got {:?}", events)
+        }
+        assert!(state.step((800, 600), &GameInput::new()).events.is_empty());
+    }
+
+    #[test]
+    fn held_presses_go_off_once_they_can() {
+        let mut state = GameState::new();
+        state.orientation.angular_momentum = Vector3::zero();
+        state.cube.set_min_subcube_length(0.0);
+        let hold_at = |pointer| GameInput { explode_subcube: true, pointer: Some(pointer), ..GameInput::new() };
+
+        // Held over empty space, then dragged onto the cube
+        for _ in 0..25 {
+            assert!(state.step((800, 600), &hold_at((0.99, 0.99))).events.is_empty());
+        }
+        assert_eq!(state.step((800, 600), &hold_at((0.0, 0.0))).events.len(), 1);
+        state.step((800, 600), &GameInput::new());
+
+        // Pressed again during the cooldown, it goes off as soon as that's
+        // over rather than waiting out a drill_interval too
+        state.explode_cooldown = 4.0 / GameState::steps_per_second() as f32;
+        state.pick_tolerance = std::f32::consts::FRAC_PI_2;
+        let fired = (2..60).find(|_| !state.step((800, 600), &hold_at((0.0, 0.0))).events.is_empty());
+        assert!(matches!(fired, Some(4..=5)), "went off {:?} steps after the last explosion", fired);
+    }
+
+    #[test]
+    fn holding_explode_drills_the_same_at_any_frame_rate() {
+        use crate::util::clock::{FakeClock, FixedTimestep};
+
+        // Steps each explosion happened in, over two seconds of holding explode
+        let drill = |fps: u32| {
+            let mut state = GameState::new();
+            state.cube.set_min_subcube_length(0.0);
+            // Always pick something, wherever the shards are hurled
+            state.pick_tolerance = std::f32::consts::FRAC_PI_2;
+            let hold = GameInput { explode_subcube: true, pointer: Some((0.0, 0.0)), ..GameInput::new() };
+
+            let mut clock = FakeClock::new();
+            let mut timestep = FixedTimestep::new(GameState::steps_per_second());
+            let mut explosions = Vec::new();
+            let mut step = 0;
+            for _ in 0..fps * 2 {
+                for _ in 0..timestep.steps(&clock) {
+                    if !state.step((800, 600), &hold).events.is_empty() {
+                        explosions.push(step);
+                    }
+                    step += 1;
+                }
+                clock.advance(1.0 / fps as f64);
+            }
+            (explosions, state.cube.subcubes.len())
+        };
+
+        let (explosions, len) = drill(30);
+        assert!(explosions.len() > 1);
+        assert_eq!(len, 1 + 7 * explosions.len());
+        assert_eq!(drill(144), (explosions.clone(), len));
+        assert_eq!(drill(60), (explosions, len));
+    }
+
+    #[test]
+    fn blasts_away_from_the_viewer_follow_the_view() {
+        let mut state = GameState::new();
+        state.orientation.angular_momentum = Vector3::zero();
+        state.blast_away_from_viewer = Some(8.0);
+        state.step((800, 600), &GameInput::new());
+        let before = state.cube.hurl_bias;
+        assert!((before - state.camera_forward() * 8.0).magnitude() < 1e-5);
+
+        state.step((800, 600), &GameInput { rotate_view: (0.5, 0.0), ..GameInput::new() });
+        state.step((800, 600), &GameInput::new());
+        assert!((state.cube.hurl_bias - before).magnitude() > 0.1);
+        assert!((state.cube.hurl_bias.magnitude() - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn the_magnet_follows_the_pointer_at_the_pivots_depth() {
+        let mut state = GameState::new();
+        let input = GameInput { magnet: true, pointer: Some((0.0, 0.0)), ..GameInput::new() };
+        state.step((800, 600), &input);
+        let (point, strength) = state.magnet.unwrap();
+        assert!(point.magnitude() < 1e-4);
+        assert_eq!(strength, state.magnet_strength);
+        assert_eq!(state.cube.magnet, state.magnet);
+
+        state.step((800, 600), &GameInput { pointer: Some((0.0, 0.0)), ..GameInput::new() });
+        assert_eq!(state.magnet, None);
+    }
+
+    #[test]
+    fn picks_are_reused_until_the_cube_changes() {
+        let mut state = GameState::new();
+        state.orientation.angular_momentum = Vector3::zero();
+        state.idle_spin = None;
+        let hover = GameInput { pointer: Some((0.0, 0.0)), ..GameInput::new() };
+
+        assert_eq!(state.step((800, 600), &hover).selected_subcube, Some(0));
+        let version = state.cube.version();
+        assert_eq!(state.step((800, 600), &hover).selected_subcube, Some(0));
+        assert_eq!(state.cube.version(), version);
+        assert!(state.pick_cache.is_some_and(|c| c.version == version));
+
+        // Moving the subcube out from under the pointer is seen right away
+        state.cube.for_each_subcube_mut(|s| s.pos.x += 10.0);
+        assert_eq!(state.step((800, 600), &hover).selected_subcube, None);
+        state.cube.subcubes[0].pos.x -= 10.0;
+        state.cube.mark_changed();
+        assert_eq!(state.step((800, 600), &hover).selected_subcube, Some(0));
+    }
+
+    #[test]
+    fn step_results_go_stale_when_the_cube_resets() {
+        let mut state = GameState::new_with_subdivision(2);
+        let hover = GameInput { pointer: Some((0.0, 0.0)), ..GameInput::new() };
+        let result = state.step((800, 600), &hover);
+        assert!(state.selected_subcube_transform(&result).is_some());
+
+        // Hurling moves the subcubes but keeps their indices, so the result still holds
+        state.step((800, 600), &GameInput { hurl_all: true, ..GameInput::new() });
+        assert!(state.selected_subcube_transform(&result).is_some());
+
+        state.step((800, 600), &GameInput { reset: true, ..GameInput::new() });
+        while state.cube.state_kind() != cube::CubeStateKind::Simulating {
+            state.step((800, 600), &GameInput::new());
+        }
+        assert_eq!(state.cube.subcubes.len(), 8);
+        assert!(state.selected_subcube_transform(&result).is_none());
+    }
+
+    #[test]
+    fn resetting_clears_the_selection() {
+        let mut state = GameState::new_with_subdivision(2);
+        state.select_next();
+        state.select_next();
+        assert!(state.selected().is_some());
+
+        state.step((800, 600), &GameInput { reset: true, ..GameInput::new() });
+        while state.cube.state_kind() != cube::CubeStateKind::Simulating {
+            state.step((800, 600), &GameInput::new());
+        }
+        assert_eq!(state.selected(), None);
+        // Tab starts over from the first rather than going on from a stale index
+        let mut fresh = GameState::new_with_subdivision(2);
+        fresh.select_next();
+        state.select_next();
+        let segment = |state: &GameState| state.cube.subcubes[state.selected().unwrap()].segment;
+        assert_eq!(segment(&state), segment(&fresh));
+    }
+
+    #[test]
+    fn selection_cycles_in_reading_order_and_wraps() {
+        let mut state = GameState::new_with_subdivision(2);
+        assert_eq!(state.selected(), None);
+
+        let mut seen = Vec::new();
+        for _ in 0..8 {
+            state.select_next();
+            seen.push(state.cube.subcubes[state.selected().unwrap()].segment);
+        }
+        assert!(seen.windows(2).all(|w| (w[0].z, w[0].y, w[0].x) < (w[1].z, w[1].y, w[1].x)));
+
+        let last = state.selected();
+        state.select_next();
+        state.select_prev();
+        assert_eq!(state.selected(), last);
+
+        // Exploding it with no pointer leaves the first shard selected
+        let input = GameInput { explode_selected: true, ..GameInput::new() };
+        assert_eq!(state.step((800, 600), &input).events.len(), 1);
+        assert_eq!(state.cube.subcubes.len(), 15);
+        assert_eq!(state.selected(), last);
+    }
+
+    #[test]
+    fn a_z_up_scene_looks_like_a_y_up_one() {
+        let y_up = GameState::new();
+        let mut z_up = GameState::new();
+        z_up.set_up_axis(Vector3::new(0.0, 0.0, 2.0));
+        assert_eq!(z_up.up_axis(), Vector3::unit_z());
+        assert!(z_up.camera_up().dot(Vector3::unit_z()) > 0.9);
+
+        // Every point of the Y-up scene lands where its turned counterpart does
+        let viewport = (800, 600);
+        let (a, b) = (y_up.solve_projection_view(viewport), z_up.solve_projection_view(viewport));
+        for p in [Vector3::new(0.5, 0.5, 0.5), Vector3::new(-0.5, 0.5, 0.0), Vector3::unit_y()] {
+            let (pa, pb) = (a * p.extend(1.0), b * (z_up.up_rotation() * p).extend(1.0));
+            assert!((pa - pb).magnitude() < 1e-4, "{:?} != {:?}", pa, pb);
+        }
+
+        // Upright: the top of the cube is above its bottom on screen
+        let screen_y = |p: Vector3<f32>| { let c = b * p.extend(1.0); c.y / c.w };
+        assert!(screen_y(Vector3::unit_z() * 0.5) > screen_y(-Vector3::unit_z() * 0.5));
+    }
+
+    #[test]
+    fn removing_subcubes_keeps_the_selection_on_the_same_one() {
+        let mut state = GameState::new_with_subdivision(2);
+        for _ in 0..8 {
+            state.select_next();
+        }
+        let segment = state.cube.subcubes[state.selected().unwrap()].segment;
+
+        state.retain_subcubes(|s| s.segment == segment || s.pos.x > 0.0);
+        assert_eq!(state.cube.subcubes.len(), if segment.x > 0.0 { 4 } else { 5 });
+        assert_eq!(state.cube.subcubes[state.selected().unwrap()].segment, segment);
+
+        state.retain_subcubes(|s| s.segment != segment);
+        assert_eq!(state.selected(), None);
+
+        // Removing straight from the cube clears the selection, even when
+        // its index is still in range, rather than moving it to another subcube
+        state.select_prev();
+        state.select_prev();
+        state.cube.retain_subcubes(|s| s.pos.y > 0.0 || s.pos.x > 0.0);
+        assert!(state.cube.subcubes.len() > 1);
+        assert_eq!(state.selected(), None);
+
+        // And exploding with nothing left doesn't panic either
+        state.select_next();
+        state.cube.retain_subcubes(|_| false);
+        assert_eq!(state.selected(), None);
+        let input = GameInput { explode_selected: true, pointer: Some((0.0, 0.0)), ..GameInput::new() };
+        assert_eq!(state.step((800, 600), &input).selected_subcube, None);
+    }
+
+    #[test]
+    fn palette_modes_cycle_and_survive_resets_and_rearranges() {
+        let mut state = GameState::new_with_subdivision(2);
+        let cycle = GameInput { cycle_palette_mode: true, ..GameInput::new() };
+
+        state.step((800, 600), &cycle);
+        assert_eq!(state.palette_mode, Some(PaletteMode::ByFace));
+        assert!(state.cube.subcubes.iter().all(|s| s.color().is_some()));
+
+        // Resets bring back uncolored subcubes, colored on the next step
+        state.step((800, 600), &GameInput { reset: true, ..GameInput::new() });
+        while state.cube.state_kind() != cube::CubeStateKind::Simulating {
+            state.step((800, 600), &GameInput::new());
+        }
+        state.step((800, 600), &GameInput::new());
+        assert!(state.cube.subcubes.iter().all(|s| s.color().is_some()));
+
+        // Rearranging changes neither the subcubes nor their count, so the
+        // colors have to survive it without being applied again
+        state.step((800, 600), &GameInput { hurl_all: true, ..GameInput::new() });
+        state.step((800, 600), &GameInput { rearrange: true, ..GameInput::new() });
+        while state.cube.state_kind() != cube::CubeStateKind::Simulating {
+            state.step((800, 600), &GameInput::new());
+        }
+        assert!(state.cube.subcubes.iter().all(|s| s.color().is_some()));
+
+        for _ in 0..3 {
+            state.step((800, 600), &cycle);
+        }
+        assert_eq!(state.palette_mode, None);
+        assert!(state.cube.subcubes.iter().all(|s| s.color().is_none()));
+    }
+
+    #[test]
+    fn coloring_by_depth_follows_splits_only() {
+        let mut state = GameState::new_with_subdivision(2);
+        state.orientation.angular_momentum = Vector3::zero();
+        state.idle_spin = None;
+        state.palette_mode = Some(PaletteMode::ByDepth);
+        state.step((800, 600), &GameInput::new());
+        let palette = Palette::rubiks();
+        let depth_color = |depth| palette.get(PaletteMode::ByDepth.index(Vector3::zero(), depth, 6));
+        assert!(state.cube.subcubes.iter().all(|s| s.color() == Some(depth_color(1))));
+
+        // Nothing splits, so nothing is recolored and picks are kept
+        let version = state.cube.version();
+        state.step((800, 600), &GameInput::new());
+        assert_eq!(state.cube.version(), version);
+
+        let explode = GameInput { explode_subcube: true, pointer: Some((0.0, 0.0)), ..GameInput::new() };
+        state.step((800, 600), &explode);
+        state.step((800, 600), &GameInput::new());
+        assert!(state.cube.subcubes.len() > 8);
+        for s in &state.cube.subcubes {
+            assert_eq!(s.color(), Some(depth_color(s.depth())));
+        }
+    }
+
+    #[test]
+    fn saving_a_loaded_save_gives_it_back() {
+        let mut state = GameState::new_with_subdivision(2);
+        let input = GameInput { hurl_all: true, rotate_view: (0.3, 0.1), zoom_view_change: 0.5, ..GameInput::new() };
+        state.step((800, 600), &input);
+        for _ in 0..10 {
+            state.step((800, 600), &GameInput::new());
+        }
+        state.shadow_mode = ShadowMode::Mapped;
+        state.render_style = RenderStyle::Points;
+        let save = state.save();
+
+        let mut loaded = GameState::new();
+        loaded.load(save.clone());
+        assert_eq!(loaded.save(), save);
+        assert_eq!(loaded.cube.subcubes, state.cube.subcubes);
+        assert_eq!(loaded.cube.initial_subcubes(), state.cube.initial_subcubes());
+        assert_eq!(loaded.cube.initial_subcubes().len(), 8);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saves_round_trip_through_json() {
+        let mut state = GameState::new_with_subdivision(2);
+        state.step((800, 600), &GameInput { hurl_all: true, rotate_view: (0.3, 0.1), ..GameInput::new() });
+        for _ in 0..10 {
+            state.step((800, 600), &GameInput::new());
+        }
+        state.shading_mode = ShadingMode::Depth;
+        state.seed = Some(7);
+
+        let json = serde_json::to_string(&state.save()).unwrap();
+        let mut loaded = GameState::new();
+        loaded.load(serde_json::from_str(&json).unwrap());
+
+        assert_eq!(loaded.cube.subcubes, state.cube.subcubes);
+        assert_eq!(loaded.cube.initial_subcubes(), state.cube.initial_subcubes());
+        assert_eq!(loaded.save(), state.save());
+        // And it steps on from there the same way
+        state.step((800, 600), &GameInput::new());
+        loaded.step((800, 600), &GameInput::new());
+        assert_eq!(loaded.cube.subcubes, state.cube.subcubes);
+    }
+}
@@ -0,0 +1,114 @@
+//! Lists of colors, and ways of handing them out to subcubes.
+
+use cgmath::Vector3;
+
+pub struct Palette {
+    colors: Vec<Vector3<f32>>
+}
+
+impl Palette {
+    /// # Panics
+    /// Panics if `colors` is empty.
+    pub fn new(colors: Vec<Vector3<f32>>) -> Palette {
+        assert!(!colors.is_empty());
+        Palette { colors }
+    }
+
+    /// Rubik's cube colors, in `PaletteMode::ByFace` order:
+    /// red and orange on x, white and yellow on y, green and blue on z.
+    pub fn rubiks() -> Palette {
+        Palette::new(vec![
+            Vector3::new(0.75, 0.05, 0.1),
+            Vector3::new(1.0, 0.45, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(1.0, 0.85, 0.0),
+            Vector3::new(0.0, 0.6, 0.3),
+            Vector3::new(0.0, 0.3, 0.7)
+        ])
+    }
+
+    pub fn colors(&self) -> &[Vector3<f32>] {
+        &self.colors
+    }
+
+    /// The color at `index`, wrapping around past the end.
+    pub fn get(&self, index: usize) -> Vector3<f32> {
+        self.colors[index % self.colors.len()]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::rubiks()
+    }
+}
+
+/// How `Cube::apply_palette` picks each subcube's color.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PaletteMode {
+    /// By the face of the original cube the subcube came from, in the order
+    /// +x, -x, +y, -y, +z, -z. Each subcube gets one color for all its faces,
+    /// so edge and corner pieces show their outermost face's color around
+    /// the edges too, unlike a real Rubik's cube.
+    ByFace,
+    /// By how many times the subcube has been split, see `Subcube::depth`
+    ByDepth,
+    /// At random, using `quad_rand`
+    Random
+}
+
+impl PaletteMode {
+    /// The next mode to cycle to, or None to go back to not using a palette.
+    pub fn next(self) -> Option<PaletteMode> {
+        match self {
+            PaletteMode::ByFace => Some(PaletteMode::ByDepth),
+            PaletteMode::ByDepth => Some(PaletteMode::Random),
+            PaletteMode::Random => None
+        }
+    }
+
+    /// Where in the palette a subcube's color comes from, given where it
+    /// belongs in the cube and its depth.
+    pub fn index(self, segment: Vector3<f32>, depth: u32, palette_len: usize) -> usize {
+        match self {
+            PaletteMode::ByFace => {
+                // The axis the subcube is furthest out along picks the face.
+                // Ties, like the middle of the cube, go to the earlier axis.
+                let abs = [segment.x.abs(), segment.y.abs(), segment.z.abs()];
+                let axis = (0..3).fold(0, |best, i| if abs[i] > abs[best] { i } else { best });
+                axis * 2 + (segment[axis] < 0.0) as usize
+            },
+            PaletteMode::ByDepth => depth as usize,
+            PaletteMode::Random => quad_rand::gen_range(0, palette_len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn by_face_picks_the_outermost_axis() {
+        let index = |x, y, z| PaletteMode::ByFace.index(Vector3::new(x, y, z), 2, 6);
+        assert_eq!(index(0.4, 0.1, -0.2), 0);
+        assert_eq!(index(-0.4, 0.1, -0.2), 1);
+        assert_eq!(index(0.1, 0.3, 0.0), 2);
+        assert_eq!(index(0.1, -0.3, 0.0), 3);
+        assert_eq!(index(0.0, 0.1, 0.375), 4);
+        assert_eq!(index(0.0, 0.1, -0.375), 5);
+        assert_eq!(index(0.0, 0.0, 0.0), 0);
+    }
+
+    #[test]
+    fn by_depth_counts_splits() {
+        let index = |depth| PaletteMode::ByDepth.index(Vector3::new(0.0, 0.0, 0.0), depth, 6);
+        assert_eq!([index(0), index(1), index(2), index(4)], [0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn palettes_wrap_around() {
+        let palette = Palette::new(vec![Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)]);
+        assert_eq!(palette.get(3), palette.get(1));
+    }
+}
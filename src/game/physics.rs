@@ -1,9 +1,11 @@
-///! Note that "Physics" is in massive quotation marks.
-///! This does not aim to be a realistic MKS physics simulation.
+//! Note that "Physics" is in massive quotation marks.
+//! This does not aim to be a realistic MKS physics simulation.
 
-use cgmath::{Quaternion, Vector3, BaseFloat, InnerSpace};
+use cgmath::{Quaternion, Vector3, BaseFloat, InnerSpace, VectorSpace};
 use num::traits::{Float, Zero, One};
 
+/// Get how much is left of something decaying at `decay` per second after `time` seconds.
+/// `decay` must range from 0 to 1.
 fn integrate_decay<T: Float + One>(decay: T, time: T) -> T {
     // x^(1/time) = 1-decay
     // x = (1-decay)^time
@@ -11,6 +13,23 @@ fn integrate_decay<T: Float + One>(decay: T, time: T) -> T {
     (one - decay).powf(time)
 }
 
+/// Get how much of the remaining distance is covered when approaching at `rate` per second
+/// for `time` seconds. `rate` must range from 0 to 1.
+pub fn approach_amount<T: Float + One>(rate: T, time: T) -> T {
+    let one: T = One::one();
+    one - integrate_decay(rate, time)
+}
+
+/// Shorten the vector to `max` if it's any longer, preserving its direction.
+pub fn clamp_magnitude<T: BaseFloat>(v: Vector3<T>, max: T) -> Vector3<T> {
+    let magnitude = v.magnitude();
+    if magnitude > max {
+        v * (max / magnitude)
+    } else {
+        v
+    }
+}
+
 pub struct ScalarMotion<T> {
     pub scalar: T,
     pub change: T,
@@ -21,9 +40,9 @@ pub struct ScalarMotion<T> {
 impl<T: Float> ScalarMotion<T> {
     pub fn new(scalar: T, change: T, decay: T) -> ScalarMotion<T> {
         ScalarMotion {
-            scalar: scalar,
-            change: change,
-            decay: decay
+            scalar,
+            change,
+            decay
         }
     }
     pub fn step(&mut self, frac: T) {
@@ -32,6 +51,26 @@ impl<T: Float> ScalarMotion<T> {
     }
 }
 
+pub struct VectorApproach<T> {
+    pub vector: Vector3<T>,
+    pub target: Vector3<T>,
+    /// How much of the remaining distance to `target` is covered every second as a percentage.
+    /// Ranges from 0 to 1. 0 never moves, 1 arrives immediately.
+    pub rate: T
+}
+impl<T: BaseFloat> VectorApproach<T> {
+    pub fn new(vector: Vector3<T>, rate: T) -> VectorApproach<T> {
+        VectorApproach {
+            vector,
+            target: vector,
+            rate
+        }
+    }
+    pub fn step(&mut self, frac: T) {
+        self.vector = self.vector.lerp(self.target, approach_amount(self.rate, frac));
+    }
+}
+
 pub struct QuaternionMotion<T: BaseFloat> {
     pub quaternion: Quaternion<T>,
     pub angular_momentum: Vector3<T>,
@@ -41,15 +80,21 @@ pub struct QuaternionMotion<T: BaseFloat> {
 impl<T: BaseFloat> QuaternionMotion<T> {
     pub fn new(quaternion: Quaternion<T>, angular_momentum: Vector3<T>, decay: T) -> QuaternionMotion<T> {
         QuaternionMotion {
-            quaternion: quaternion,
-            angular_momentum: angular_momentum,
-            decay: decay
+            quaternion,
+            angular_momentum,
+            decay
         }
     }
     pub fn step(&mut self, frac: T) {
-        let q_angular_momentum = Quaternion::from_sv(Zero::zero(), self.angular_momentum * frac);
-        let d_quaternion = q_angular_momentum * self.quaternion;
-        self.quaternion = (self.quaternion + d_quaternion).normalize();
-        self.angular_momentum = self.angular_momentum * integrate_decay(self.decay, frac);
+        // Compose with the exact rotation for the step, exp(angular_momentum * frac),
+        // rather than adding the derivative. Both are unit quaternions, so
+        // renormalizing only mops up rounding and nothing drifts over long runs.
+        let v = self.angular_momentum * frac;
+        let angle = v.magnitude();
+        if angle > Zero::zero() {
+            let d_quaternion = Quaternion::from_sv(angle.cos(), v * (angle.sin() / angle));
+            self.quaternion = (d_quaternion * self.quaternion).normalize();
+        }
+        self.angular_momentum *= integrate_decay(self.decay, frac);
     }
 }
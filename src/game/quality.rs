@@ -0,0 +1,115 @@
+//! Coarser explosions while frames are taking too long.
+
+use super::GameState;
+
+/// Limits how finely subcubes are exploded while the slowest frames are over
+/// a time budget, and lifts the limits once there's headroom again.
+///
+/// The limits it raises and lowers are `GameState::explode_subdivisions` and
+/// `Cube::min_subcube_length`. Whatever they were when it stepped in is what
+/// they go back to.
+pub struct AdaptiveQuality {
+    /// Seconds the 95th percentile frame time can take before the limits apply
+    pub budget: f64,
+    /// The limits are lifted once the 95th percentile is under this fraction of `budget`
+    pub headroom: f64,
+    /// The most ways subcubes split while limited
+    pub max_subdivisions: u32,
+    /// The smallest subcubes that split while limited
+    pub min_subcube_length: f32,
+    /// The settings from before limiting, if limited
+    saved: Option<(u32, f32)>
+}
+
+impl AdaptiveQuality {
+    pub fn new(budget: f64) -> AdaptiveQuality {
+        AdaptiveQuality {
+            budget,
+            headroom: 0.75,
+            max_subdivisions: 2,
+            min_subcube_length: 1.0/4.0,
+            saved: None
+        }
+    }
+
+    pub fn is_limiting(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Apply or lift the limits on `state` given the current 95th percentile
+    /// frame time, if there is one yet. Returns true if that changed anything.
+    pub fn update(&mut self, p95: Option<f64>, state: &mut GameState) -> bool {
+        let Some(p95) = p95 else {
+            return false;
+        };
+
+        match self.saved {
+            None if p95 > self.budget => {
+                let min_length = state.cube.min_subcube_length();
+                self.saved = Some((state.explode_subdivisions, min_length));
+                state.explode_subdivisions = state.explode_subdivisions.min(self.max_subdivisions);
+                state.cube.set_min_subcube_length(min_length.max(self.min_subcube_length));
+                true
+            },
+            Some((subdivisions, min_length)) if p95 < self.budget * self.headroom => {
+                state.explode_subdivisions = subdivisions;
+                state.cube.set_min_subcube_length(min_length);
+                self.saved = None;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameInput;
+
+    #[test]
+    fn limits_apply_over_budget_and_lift_with_headroom() {
+        let mut state = GameState::new();
+        state.explode_subdivisions = 4;
+        let mut quality = AdaptiveQuality::new(0.016);
+
+        assert!(!quality.update(None, &mut state));
+        assert!(!quality.update(Some(0.010), &mut state));
+
+        assert!(quality.update(Some(0.020), &mut state));
+        assert!(quality.is_limiting());
+        assert_eq!(state.explode_subdivisions, 2);
+        assert_eq!(state.cube.min_subcube_length(), 0.25);
+
+        // Under budget, but not by enough to lift the limits
+        assert!(!quality.update(Some(0.015), &mut state));
+        assert!(quality.is_limiting());
+
+        assert!(quality.update(Some(0.010), &mut state));
+        assert!(!quality.is_limiting());
+        assert_eq!(state.explode_subdivisions, 4);
+        assert_eq!(state.cube.min_subcube_length(), 1.0/16.0);
+    }
+
+    #[test]
+    fn limited_explosions_split_fewer_ways() {
+        let explode = GameInput { explode_subcube: true, pointer: Some((0.0, 0.0)), ..GameInput::new() };
+        let explode_once = |limited: bool| {
+            let mut state = GameState::new();
+            state.explode_subdivisions = 4;
+            if limited {
+                AdaptiveQuality::new(0.016).update(Some(0.020), &mut state);
+            }
+            state.step((800, 600), &explode);
+            state.cube.subcubes.len()
+        };
+        assert_eq!(explode_once(false), 4 * 4 * 4);
+        assert_eq!(explode_once(true), 2 * 2 * 2);
+
+        // Subcubes shorter than a quarter of the cube stop splitting, and are only hurled
+        let mut state = GameState::new_with_subdivision(8);
+        AdaptiveQuality::new(0.016).update(Some(0.020), &mut state);
+        state.step((800, 600), &explode);
+        assert_eq!(state.cube.subcubes.len(), 8 * 8 * 8);
+    }
+}
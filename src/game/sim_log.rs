@@ -0,0 +1,156 @@
+//! A step-by-step record of the simulation as CSV, for plotting elsewhere.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::cube::Cube;
+
+/// How many rows are written between flushes, so a log that's never
+/// flushed at the end, such as when the program is killed, loses at most
+/// about a second of steps
+pub const FLUSH_INTERVAL: u64 = 60;
+
+/// The first row of every log
+pub const HEADER: &str = "step,subcubes,kinetic_energy,centroid_x,centroid_y,centroid_z,size_x,size_y,size_z";
+
+/// Writes a row of `Cube::stats` for every step it's given.
+///
+/// Writing never stops the simulation. If it fails, the logger keeps the
+/// error to look at with `error` and writes nothing more.
+pub struct SimLogger {
+    out: Box<dyn Write + Send>,
+    /// Rows written, not counting the header
+    steps: u64,
+    error: Option<io::Error>
+}
+
+impl SimLogger {
+    pub fn new(out: Box<dyn Write + Send>) -> SimLogger {
+        SimLogger { out, steps: 0, error: None }
+    }
+
+    /// Log to a new file at `path`, replacing any that's there.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<SimLogger> {
+        let file = std::fs::File::create(path)?;
+        Ok(SimLogger::new(Box::new(io::BufWriter::new(file))))
+    }
+
+    /// Write a row for how `cube` is now, after the header if it's the first.
+    pub fn log(&mut self, cube: &Cube) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.write_row(cube) {
+            self.error = Some(e);
+        }
+    }
+
+    fn write_row(&mut self, cube: &Cube) -> io::Result<()> {
+        if self.steps == 0 {
+            writeln!(self.out, "{}", HEADER)?;
+        }
+        let stats = cube.stats();
+        let (c, s) = (stats.centroid, stats.size);
+        writeln!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{}",
+            self.steps, stats.subcube_count, stats.kinetic_energy, c.x, c.y, c.z, s.x, s.y, s.z
+        )?;
+        self.steps += 1;
+        if self.steps.is_multiple_of(FLUSH_INTERVAL) {
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Why writing stopped, if it has.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Write out anything buffered. Call it before exiting, since not every
+    /// platform drops the logger on the way out.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::game::{GameInput, GameState};
+
+    /// Keeps what's written where the test can still read it, and counts flushes
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>, Arc<Mutex<u32>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            *self.1.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_a_row_per_step_that_parses_back() {
+        let out = Shared::default();
+        let mut state = GameState::new_with_subdivision(2);
+        state.sim_logger = Some(SimLogger::new(Box::new(out.clone())));
+
+        state.step((800, 600), &GameInput { hurl_all: true, ..GameInput::new() });
+        for _ in 0..4 {
+            state.step((800, 600), &GameInput::new());
+        }
+
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(HEADER));
+        let rows: Vec<Vec<f32>> = lines.map(|line| line.split(',').map(|v| v.parse().unwrap()).collect()).collect();
+        assert_eq!(rows.len(), 5);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row.len(), HEADER.split(',').count());
+            assert_eq!((row[0], row[1]), (i as f32, 8.0));
+            // Hurled pieces are moving and spreading out
+            assert!(row[2] > 0.0);
+            assert!(row[6] > 1.0);
+        }
+    }
+
+    #[test]
+    fn flushes_every_interval() {
+        let out = Shared::default();
+        let mut logger = SimLogger::new(Box::new(out.clone()));
+        let cube = Cube::new();
+        for _ in 0..FLUSH_INTERVAL * 2 + 1 {
+            logger.log(&cube);
+        }
+        assert_eq!(*out.1.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn write_errors_are_kept_and_stop_logging() {
+        struct Failing;
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut logger = SimLogger::new(Box::new(Failing));
+        logger.log(&Cube::new());
+        logger.log(&Cube::new());
+        assert_eq!(logger.error().map(|e| e.to_string()), Some("disk full".to_string()));
+        assert_eq!(logger.steps(), 0);
+    }
+}
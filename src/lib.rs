@@ -0,0 +1,7 @@
+//! The cube simulation and its supporting utilities, without any windowing
+//! or rendering. The `rust-cubes-demo` binary draws it with miniquad.
+
+pub mod game;
+pub mod util;
+#[cfg(feature = "ffi")]
+pub mod ffi;
@@ -1,11 +1,26 @@
+mod args;
+
 use miniquad::*;
 
+use std::collections::VecDeque;
+
 use glam::{Mat4, Vec3};
+use cgmath::{EuclideanSpace, InnerSpace, SquareMatrix};
 
-pub mod game;
-pub mod util;
+use cubes::game::{GameState, GameStateSave, GameStepResult, GameEvent, GameInput, OutlineMode, RenderStyle, ShadingMode, ShadowMode};
+use cubes::game::cube::{CubeStateKind, SubcubeRender, SubcubeShape};
+use cubes::game::demo::DemoScript;
+use cubes::game::attract::AttractMode;
+use cubes::game::action::Action;
+use cubes::game::quality::AdaptiveQuality;
+use cubes::game::sim_log::SimLogger;
+use cubes::util::clock::{Clock, SystemClock, FixedTimestep, FrameStats};
+use cubes::util::texture::Texture2D;
+use cubes::util::on_change::ValueOnChange;
+use cubes::util::matrix::MatrixBuilder;
+use cubes::util::debug_lines::DebugLines;
 
-use game::{GameState, GameStepResult, GameInput};
+use args::Args;
 
 // How much dragging the view affects rotation
 #[cfg(target_arch = "wasm32")]
@@ -19,19 +34,773 @@ static ZOOM_COEFF: f32 = 1.0/100.0;
 #[cfg(not(target_arch = "wasm32"))]
 static ZOOM_COEFF: f32 = 0.5;
 
-struct Stage {
-    ctx: Box<dyn RenderingBackend>,
+// How much holding a zoom key affects zoom, per second
+static KEY_ZOOM_RATE: f32 = 2.0;
+
+// How far holding an arrow key moves the reticle, in pointer coordinates per second,
+// and the length of its arms in pixels
+static RETICLE_SPEED: f32 = 0.75;
+static RETICLE_SIZE: f32 = 12.0;
+
+// How many degrees the field of view changes per key press
+static FOV_STEP: f32 = 5.0;
+
+// What the process exits with once the window closes, set when something
+// goes wrong that should still let everything shut down properly first
+static EXIT_CODE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+// Maximum seconds between two clicks for them to count as a double-click
+static DOUBLE_CLICK_TIME: f64 = 0.3;
+
+// The distance and speed that map to the far end of the debug colormap
+static DEPTH_SHADE_MAX: f32 = 4.0;
+static SPEED_SHADE_MAX: f32 = 8.0;
+
+// The opacity of the ghost overlay's subcubes
+static GHOST_ALPHA: f32 = 0.15;
+
+// Where drop shadows fall, and the light casting them
+static SHADOW_FLOOR_Y: f32 = -1.0;
+static SHADOW_LIGHT_POS: [f32; 3] = [0.5, 6.0, 1.0];
+static SHADOW_ALPHA: f32 = 0.4;
+
+// The shadow map's resolution, and the half-width of the area around the origin it covers
+static SHADOW_MAP_SIZE: u32 = 1024;
+static SHADOW_MAP_RADIUS: f32 = 4.0;
+
+// How many times the sphere mesh's icosahedron is split. Each split quadruples the triangles.
+static SPHERE_SUBDIVISIONS: u32 = 2;
+
+// The length of the drawn picking ray, and the size of the marker at its hit point
+static DEBUG_RAY_LENGTH: f32 = 10.0;
+static DEBUG_MARKER_SIZE: f32 = 0.05;
+// The height of index labels, and how far toward the camera they float, in subcube lengths
+static INDEX_LABEL_SIZE: f32 = 0.3;
+static INDEX_LABEL_OFFSET: f32 = 0.9;
+
+// Frame time percentiles printed by --verbose: over how many frames, and how often in seconds
+static FRAME_STATS_WINDOW: usize = 600;
+static FRAME_STATS_INTERVAL: f64 = 5.0;
+
+/// How a cube pipeline combines its fragments with what's already drawn.
+#[derive(Copy, Clone)]
+enum Blend {
+    /// Overwrite, and write depth
+    Opaque,
+    /// Blend by alpha, and still write depth. For textures with alpha.
+    Alpha,
+    /// Blend by alpha without writing depth. For translucent subcubes,
+    /// which must be drawn after the opaque ones, back to front.
+    Translucent
+}
+
+/// The cube pipeline, for each combination of back-face culling and blending.
+struct CubePipelines {
+    /// Indexed by `[cull_faces][blend]`
+    variants: [[Pipeline; 3]; 2]
+}
+
+impl CubePipelines {
+    /// Compile the shaders and build the pipelines from them.
+    fn new(ctx: &mut dyn RenderingBackend, vertex: &str, fragment: &str) -> Result<CubePipelines, ShaderError> {
+        let shader = ctx.new_shader(
+            ShaderSource::Glsl {
+                vertex,
+                fragment,
+            },
+            shader::meta()
+        )?;
+
+        let mut new_pipeline = |cull_faces: bool, blend: Blend| ctx.new_pipeline(
+            &[
+                BufferLayout::default(),
+                BufferLayout {
+                    step_func: VertexStep::PerInstance,
+                    ..Default::default()
+                },
+                BufferLayout::default(),
+            ],
+            &[
+                VertexAttribute::with_buffer("position", VertexFormat::Float3, 0),
+                VertexAttribute::with_buffer("in_inst_pos", VertexFormat::Float3, 1),
+                VertexAttribute::with_buffer("normal", VertexFormat::Float3, 2),
+            ],
+            shader,
+            PipelineParams {
+                depth_test: Comparison::LessOrEqual,
+                depth_write: !matches!(blend, Blend::Translucent),
+                cull_face: if cull_faces { CullFace::Back } else { CullFace::Nothing },
+                // The OpenGL default, and what CubeArrays produces
+                front_face_order: FrontFaceOrder::CounterClockwise,
+                color_blend: match blend {
+                    Blend::Opaque => None,
+                    Blend::Alpha | Blend::Translucent => Some(BlendState::new(
+                        Equation::Add,
+                        BlendFactor::Value(BlendValue::SourceAlpha),
+                        BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
+                    ))
+                },
+                ..Default::default()
+            }
+        );
+
+        Ok(CubePipelines {
+            variants: [false, true].map(|cull_faces| {
+                [Blend::Opaque, Blend::Alpha, Blend::Translucent].map(|blend| new_pipeline(cull_faces, blend))
+            })
+        })
+    }
+
+    fn get(&self, cull_faces: bool, blend: Blend) -> &Pipeline {
+        &self.variants[cull_faces as usize][blend as usize]
+    }
+
+    #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+    fn delete(self, ctx: &mut dyn RenderingBackend) {
+        for pipeline in self.variants.into_iter().flatten() {
+            ctx.delete_pipeline(pipeline);
+        }
+    }
+}
+
+#[repr(C)]
+struct LineVertex {
+    position: Vec3,
+    // Not a `Vec4`, which is 16-aligned and would pad `position` out of
+    // the tightly packed layout the pipeline reads
+    color: [f32; 4]
+}
+
+/// Draws the segments collected in a `DebugLines`.
+struct LineRenderer {
+    pipeline: Pipeline,
+    bindings: Bindings
+}
+
+impl LineRenderer {
+    // The most vertices that can be drawn at once. Longer lists are drawn in batches.
+    const CAPACITY: usize = 1024;
+
+    fn new(ctx: &mut dyn RenderingBackend) -> Result<LineRenderer, ShaderError> {
+        let shader = ctx.new_shader(
+            ShaderSource::Glsl {
+                vertex: line_shader::VERTEX,
+                fragment: line_shader::FRAGMENT,
+            },
+            line_shader::meta()
+        )?;
+
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("position", VertexFormat::Float3),
+                VertexAttribute::new("color", VertexFormat::Float4)
+            ],
+            shader,
+            PipelineParams {
+                depth_test: Comparison::LessOrEqual,
+                depth_write: true,
+                primitive_type: PrimitiveType::Lines,
+                ..Default::default()
+            }
+        );
+
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<LineVertex>(LineRenderer::CAPACITY),
+        );
+        let indices: Vec<u16> = (0..LineRenderer::CAPACITY as u16).collect();
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        Ok(LineRenderer {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![]
+            }
+        })
+    }
+
+    /// Draw every segment in `lines` into the current pass, then clear them.
+    fn flush(&self, ctx: &mut dyn RenderingBackend, projection_view: Mat4, srgb_output: bool, lines: &mut DebugLines) {
+        let to_glam = |p: cgmath::Point3<f32>| Vec3::new(p.x, p.y, p.z);
+        let vertices: Vec<LineVertex> = lines.segments().iter()
+            .flat_map(|segment| {
+                let color = segment.color;
+                [segment.start, segment.end].map(|p| LineVertex { position: to_glam(p), color })
+            })
+            .collect();
+        lines.clear();
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_uniforms(UniformsSource::table(&line_shader::Uniforms { projection_view, srgb_output: srgb_output as i32 }));
+        for batch in vertices.chunks(LineRenderer::CAPACITY) {
+            ctx.buffer_update(self.bindings.vertex_buffers[0], BufferSource::slice(batch));
+            ctx.apply_bindings(&self.bindings);
+            ctx.draw(0, batch.len() as i32, 1);
+        }
+    }
+}
+
+#[repr(C)]
+struct PointVertex {
+    position: Vec3,
+    size: f32,
+    color: glam::Vec4
+}
+
+/// Draws each subcube as a single round point, for `RenderStyle::Points`.
+struct PointRenderer {
+    pipeline: Pipeline,
+    bindings: Bindings
+}
+
+impl PointRenderer {
+    // The most points that can be drawn at once. More are drawn in batches.
+    const CAPACITY: usize = 4096;
+
+    fn new(ctx: &mut dyn RenderingBackend) -> Result<PointRenderer, ShaderError> {
+        let shader = ctx.new_shader(
+            ShaderSource::Glsl {
+                vertex: point_shader::VERTEX,
+                fragment: point_shader::FRAGMENT,
+            },
+            point_shader::meta()
+        )?;
+
+        // Desktop OpenGL ignores gl_PointSize unless asked not to. GLES and WebGL always use it.
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            miniquad::gl::glEnable(miniquad::gl::GL_PROGRAM_POINT_SIZE);
+        }
+
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("position", VertexFormat::Float3),
+                VertexAttribute::new("size", VertexFormat::Float1),
+                VertexAttribute::new("color", VertexFormat::Float4)
+            ],
+            shader,
+            PipelineParams {
+                depth_test: Comparison::LessOrEqual,
+                depth_write: true,
+                primitive_type: PrimitiveType::Points,
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
+                )),
+                ..Default::default()
+            }
+        );
+
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<PointVertex>(PointRenderer::CAPACITY),
+        );
+        let indices: Vec<u16> = (0..PointRenderer::CAPACITY as u16).collect();
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        Ok(PointRenderer {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![]
+            }
+        })
+    }
+
+    /// Draw `subcubes` into the current pass. `point_scale` is how many
+    /// pixels a world unit covers at a distance of 1 from the camera.
+    fn draw(&self, ctx: &mut dyn RenderingBackend, projection_view: Mat4, point_scale: f32, srgb_output: bool, subcubes: &[SubcubeRender]) {
+        let vertices: Vec<PointVertex> = subcubes.iter()
+            .map(|subcube| PointVertex {
+                position: Vec3::new(subcube.pos.x, subcube.pos.y, subcube.pos.z),
+                size: subcube.subcube_length,
+                color: subcube_rgb(subcube).extend(subcube.alpha)
+            })
+            .collect();
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_uniforms(UniformsSource::table(&point_shader::Uniforms { projection_view, point_scale, srgb_output: srgb_output as i32 }));
+        for batch in vertices.chunks(PointRenderer::CAPACITY) {
+            ctx.buffer_update(self.bindings.vertex_buffers[0], BufferSource::slice(batch));
+            ctx.apply_bindings(&self.bindings);
+            ctx.draw(0, batch.len() as i32, 1);
+        }
+    }
+}
+
+/// The color `fragment.glsl` gives a subcube without a texture or debug shading.
+fn subcube_rgb(subcube: &SubcubeRender) -> Vec3 {
+    if let Some(c) = subcube.color {
+        return Vec3::new(c.x, c.y, c.z);
+    }
+    // Hue and saturation from 0 to 1, and value from 0.25 to 1
+    let v = subcube.color_coord;
+    let (h, s, value) = (v.x, v.y, 0.25 + 0.75 * v.z);
+    let p = ((Vec3::splat(h) + Vec3::new(1.0, 2.0 / 3.0, 1.0 / 3.0)).fract() * 6.0 - Vec3::splat(3.0)).abs();
+    value * Vec3::ONE.lerp((p - Vec3::ONE).clamp(Vec3::ZERO, Vec3::ONE), s)
+}
+
+/// Draws the scene from an offscreen target, darkening edges it finds in the depth.
+struct ScreenSpaceOutlines {
     pipeline: Pipeline,
     bindings: Bindings,
+    /// The offscreen target and the size it was made for
+    target: Option<(RenderPass, (u32, u32))>
+}
+
+impl ScreenSpaceOutlines {
+    fn new(ctx: &mut dyn RenderingBackend) -> Result<ScreenSpaceOutlines, ShaderError> {
+        let shader = ctx.new_shader(
+            ShaderSource::Glsl {
+                vertex: outline_shader::VERTEX,
+                fragment: outline_shader::FRAGMENT,
+            },
+            outline_shader::meta()
+        )?;
+
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[VertexAttribute::new("position", VertexFormat::Float2)],
+            shader,
+            PipelineParams::default()
+        );
+
+        let quad: [f32; 8] = [-1.0, -1.0,   1.0, -1.0,   1.0, 1.0,   -1.0, 1.0];
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&quad),
+        );
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&[0u16, 1, 2,   0, 2, 3]),
+        );
+
+        Ok(ScreenSpaceOutlines {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                // Replaced by the target's textures once it exists
+                images: vec![]
+            },
+            target: None
+        })
+    }
+
+    /// Get the pass to draw the scene into, remaking it if the window changed size.
+    fn pass(&mut self, ctx: &mut dyn RenderingBackend, size: (u32, u32)) -> RenderPass {
+        match self.target {
+            Some((pass, target_size)) if target_size == size => return pass,
+            Some((pass, _)) => ctx.delete_render_pass(pass),
+            None => ()
+        }
+
+        let (width, height) = size;
+        let params = |format| TextureParams {
+            width,
+            height,
+            format,
+            wrap: TextureWrap::Clamp,
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            ..Default::default()
+        };
+        let color = ctx.new_render_texture(params(TextureFormat::RGBA8));
+        // Finding creases needs more precision than 16 bits of depth, but
+        // WebGL 1 depth textures can't be floats
+        let depth_format = if cfg!(target_arch = "wasm32") { TextureFormat::Depth } else { TextureFormat::Depth32 };
+        let depth = ctx.new_render_texture(params(depth_format));
+        let pass = ctx.new_render_pass(color, Some(depth));
+
+        self.bindings.images = vec![color, depth];
+        self.target = Some((pass, size));
+        pass
+    }
+
+    /// Draw the scene rendered into `pass()` to the current pass.
+    /// `near` and `far` are the projection's, as in `GameState::projection_clip_planes`.
+    fn draw(&self, ctx: &mut dyn RenderingBackend, (near, far): (f32, f32), orthographic: bool) {
+        let Some((_, (width, height))) = self.target else {
+            return;
+        };
+
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.apply_uniforms(UniformsSource::table(&outline_shader::Uniforms {
+            texel_size: glam::Vec2::new(1.0 / width as f32, 1.0 / height as f32),
+            near,
+            far,
+            orthographic: orthographic as i32
+        }));
+        ctx.draw(0, 6, 1);
+    }
+}
+
+/// Keeps the last frame around and draws it faded under the next one, so
+/// moving subcubes leave trails. Draws to two targets in turn, since a
+/// target can't be read while it's drawn into.
+struct Trails {
+    pipeline: Pipeline,
+    bindings: Bindings,
+    targets: Option<TrailTargets>,
+    /// Which target the next frame draws into
+    current: usize,
+    /// Whether the other target holds a frame yet
+    has_previous: bool
+}
+
+#[derive(Copy, Clone)]
+struct TrailTargets {
+    passes: [RenderPass; 2],
+    colors: [TextureId; 2],
+    /// The size they were made for
+    size: (u32, u32)
+}
+
+impl Trails {
+    fn new(ctx: &mut dyn RenderingBackend) -> Result<Trails, ShaderError> {
+        let shader = ctx.new_shader(
+            ShaderSource::Glsl {
+                vertex: trail_shader::VERTEX,
+                fragment: trail_shader::FRAGMENT,
+            },
+            trail_shader::meta()
+        )?;
+
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[VertexAttribute::new("position", VertexFormat::Float2)],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
+                )),
+                ..Default::default()
+            }
+        );
+
+        let quad: [f32; 8] = [-1.0, -1.0,   1.0, -1.0,   1.0, 1.0,   -1.0, 1.0];
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&quad),
+        );
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&[0u16, 1, 2,   0, 2, 3]),
+        );
+
+        Ok(Trails {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                // Replaced by a target's color texture before drawing
+                images: vec![]
+            },
+            targets: None,
+            current: 0,
+            has_previous: false
+        })
+    }
+
+    /// Get the pass to draw the next frame into, remaking the targets if the window changed size.
+    fn pass(&mut self, ctx: &mut dyn RenderingBackend, size: (u32, u32)) -> RenderPass {
+        match self.targets {
+            Some(targets) if targets.size == size => return targets.passes[self.current],
+            Some(targets) => targets.passes.into_iter().for_each(|pass| ctx.delete_render_pass(pass)),
+            None => ()
+        }
+
+        let (width, height) = size;
+        let params = |format| TextureParams {
+            width,
+            height,
+            format,
+            wrap: TextureWrap::Clamp,
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            ..Default::default()
+        };
+        let colors = [(); 2].map(|_| ctx.new_render_texture(params(TextureFormat::RGBA8)));
+        // Each has its own depth, since deleting a pass deletes its textures
+        let passes = colors.map(|color| {
+            let depth = ctx.new_render_texture(params(TextureFormat::Depth));
+            ctx.new_render_pass(color, Some(depth))
+        });
 
+        self.targets = Some(TrailTargets { passes, colors, size });
+        self.has_previous = false;
+        passes[self.current]
+    }
+
+    /// Draw the previous frame over the current pass, at `opacity`.
+    fn draw_previous(&mut self, ctx: &mut dyn RenderingBackend, opacity: f32) {
+        if self.has_previous {
+            self.draw_target(ctx, 1 - self.current, opacity);
+        }
+    }
+
+    /// Draw the frame just finished to the current pass, and keep it for the next.
+    fn draw_finished(&mut self, ctx: &mut dyn RenderingBackend) {
+        self.draw_target(ctx, self.current, 1.0);
+        self.current = 1 - self.current;
+        self.has_previous = true;
+    }
+
+    fn draw_target(&mut self, ctx: &mut dyn RenderingBackend, index: usize, opacity: f32) {
+        let Some(targets) = self.targets else {
+            return;
+        };
+        self.bindings.images = vec![targets.colors[index]];
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.apply_uniforms(UniformsSource::table(&trail_shader::Uniforms { opacity }));
+        ctx.draw(0, 6, 1);
+    }
+
+    /// Forget the previous frame, e.g. when trails are turned off, so
+    /// turning them back on doesn't bring back a stale one.
+    fn clear(&mut self) {
+        self.has_previous = false;
+    }
+}
+
+/// Depth rendered from a directional light, for shadow mapping.
+struct ShadowMap {
+    pass: RenderPass,
+    depth: TextureId,
+    /// Bound instead of the usual images while drawing into the map,
+    /// so the map isn't read and written at once
+    images: Vec<TextureId>
+}
+
+impl ShadowMap {
+    fn new(ctx: &mut dyn RenderingBackend, image_count: usize, blank_texture: TextureId) -> ShadowMap {
+        let params = |format| TextureParams {
+            width: SHADOW_MAP_SIZE,
+            height: SHADOW_MAP_SIZE,
+            format,
+            wrap: TextureWrap::Clamp,
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            ..Default::default()
+        };
+        // Unused, but render passes need a color attachment
+        let color = ctx.new_render_texture(params(TextureFormat::RGBA8));
+        let depth_format = if cfg!(target_arch = "wasm32") { TextureFormat::Depth } else { TextureFormat::Depth32 };
+        let depth = ctx.new_render_texture(params(depth_format));
+        let pass = ctx.new_render_pass(color, Some(depth));
+
+        ShadowMap {
+            pass,
+            depth,
+            images: vec![blank_texture; image_count]
+        }
+    }
+
+    /// The light's view of the area around the origin, looking along
+    /// `SHADOW_LIGHT_POS` toward it. The light is placed for a Y-up scene,
+    /// and turned by `up_rotation` to fit this one.
+    fn projection_view(up_rotation: cgmath::Quaternion<f32>) -> Mat4 {
+        let distance = SHADOW_MAP_RADIUS * 2.0;
+        let eye = Vec3::from(SHADOW_LIGHT_POS).normalize() * distance;
+        let r = SHADOW_MAP_RADIUS;
+        Mat4::orthographic_rh_gl(-r, r, -r, r, distance - r, distance + r)
+            * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y)
+            * cgmath_to_glam(up_rotation.conjugate().into())
+    }
+}
+
+/// The color behind the cube
+const BACKGROUND: [f32; 3] = [0.0, 0.0, 0.25];
+
+/// Encode a linear color as sRGB, as the shaders do when `GameState::srgb_output` is set.
+fn linear_to_srgb(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(|c| match c.clamp(0.0, 1.0) {
+        c if c < 0.0031308 => c * 12.92,
+        c => 1.055 * c.powf(1.0 / 2.4) - 0.055
+    })
+}
+
+/// Flashes per second while an explosion's fuse burns down
+const FUSE_FLASH_RATE: f32 = 4.0;
+/// Flashes per second over the last second of a fuse
+const FUSE_FLASH_RATE_FINAL: f32 = 10.0;
+
+/// Whether a subcube is lit, `remaining` seconds before its fuse runs out.
+/// Counting from the end keeps every fuse flashing in time as it goes off.
+fn fuse_flash_on(remaining: f32) -> bool {
+    let rate = if remaining < 1.0 { FUSE_FLASH_RATE_FINAL } else { FUSE_FLASH_RATE };
+    ((remaining * rate * 2.0) as u32).is_multiple_of(2)
+}
+
+/// How a subcube is tinted, passed to the fragment shader as its `highlight`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Highlight {
+    None = 0,
+    /// Under the pointer
+    Hovered = 1,
+    /// Picked out with the keyboard
+    Selected = 2,
+    /// Under the pointer while the explode button is held
+    Drilling = 3,
+    /// Waiting on an explosion's fuse, on every other flash
+    Armed = 4
+}
+
+/// An offscreen target that frames are drawn to instead of the window,
+/// for saving as a PNG.
+struct Capture {
+    pass: RenderPass,
+    color: TextureId,
+    size: (u32, u32),
+    path: String,
+    /// Steps still to run before the frame is drawn, regardless of the clock
+    steps: u32
+}
+
+impl Capture {
+    fn new(ctx: &mut dyn RenderingBackend, (width, height): (u32, u32), path: String, steps: u32) -> Capture {
+        let params = |format| TextureParams {
+            width,
+            height,
+            format,
+            ..Default::default()
+        };
+        let color = ctx.new_render_texture(params(TextureFormat::RGBA8));
+        let depth = ctx.new_render_texture(params(TextureFormat::Depth));
+        let pass = ctx.new_render_pass(color, Some(depth));
+
+        Capture {
+            pass,
+            color,
+            size: (width, height),
+            path,
+            steps
+        }
+    }
+
+    fn save(&self, ctx: &mut dyn RenderingBackend) -> Result<(), String> {
+        let (width, height) = self.size;
+        let mut rgba = vec![0; width as usize * height as usize * 4];
+        ctx.texture_read_pixels(self.color, &mut rgba);
+
+        // GL reads the bottom row first
+        let rows: Vec<u8> = rgba.chunks_exact(width as usize * 4).rev().flatten().copied().collect();
+        let image = Texture2D { width, height, rgba: rows, has_alpha: false };
+        image.save(&self.path)
+    }
+}
+
+/// Geometry to draw subcubes of one shape with.
+struct Mesh {
+    bindings: Bindings,
+    index_count: i32
+}
+
+struct LoadedTexture {
+    path: std::path::PathBuf,
+    id: TextureId,
+    /// If true, the texture has an alpha channel and needs blending
+    has_alpha: bool,
+    mipmaps: bool
+}
+
+struct Stage {
+    ctx: Box<dyn RenderingBackend>,
+    pipelines: CubePipelines,
+    /// Indexed by `SubcubeShape`. They share the instance position buffer and images.
+    meshes: [Mesh; 2],
+    line_renderer: LineRenderer,
+    point_renderer: PointRenderer,
+    /// Segments to draw over this frame's scene, in world space
+    debug_lines: DebugLines,
+    screen_space_outlines: ScreenSpaceOutlines,
+    trails: Trails,
+    shadow_map: ShadowMap,
+    blank_texture: TextureId,
+    /// The texture loaded from `GameState::texture_path`
+    texture: Option<LoadedTexture>,
+
+    clock: Box<dyn Clock>,
+    timestep: FixedTimestep,
+
+    /// Input that persists between steps, such as the pointer and held buttons
     input: GameInput,
+    /// Presses that haven't been seen by a step yet
+    presses: VecDeque<Action>,
     drag_last: Option<(f32, f32)>,
+    /// How far the view has been dragged since the last steps were run, however many motion events that took
+    drag_delta: (f32, f32),
+    /// Whether the zoom in and zoom out keys are held
+    zoom_keys: (bool, bool),
+    /// Whether the left, right, down and up arrow keys are held, to move the reticle
+    reticle_keys: [bool; 4],
+    /// When the last left click was, and where the subcube it exploded was
+    /// once a step has seen it, in case it's the first half of a double-click
+    last_left_click: Option<(f64, Option<cgmath::Vector3<f32>>)>,
+    /// Whether either shift key is held, to crack rather than explode on click
+    shift_held: bool,
     game_state: GameState,
-    game_step_result: Option<GameStepResult>
+    game_step_result: Option<GameStepResult>,
+    /// The picking ray of the last click and where it hit, while debugging picking
+    clicked_ray: Option<(collision::Ray3<f32>, Option<cgmath::Point3<f32>>)>,
+    /// A click happened that the next step's picking ray should be kept for
+    ray_clicked: bool,
+    /// Scripted input that runs while nobody is using the demo
+    demo: Option<DemoScript>,
+    /// Unattended play once nobody has used the demo for a while
+    attract: Option<AttractMode>,
+    fullscreen: bool,
+    /// Saved with F5, and brought back with F9
+    quick_save: Option<GameStateSave>,
+    /// Set if the subcube count and cube state should be printed when they change
+    trace: Option<(ValueOnChange<usize>, ValueOnChange<CubeStateKind>)>,
+    frame_stats: FrameStats,
+    /// Set if frame time percentiles should be printed, to when they last were
+    stats_printed_at: Option<f64>,
+    /// Set if explosions should get coarser while frames are slow
+    quality: Option<AdaptiveQuality>,
+    /// Set if the first frame should be saved to a file instead of shown
+    capture: Option<Capture>,
+
+    #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+    shader_watcher: Option<shader::Watcher>
 }
 
 impl Stage {
-    fn new() -> Self {
+    /// Fails if the built-in shaders don't compile or link on this platform.
+    fn new(args: &Args) -> Result<Stage, ShaderError> {
         let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
 
         let cube_arrays = CubeArrays::new();
@@ -54,48 +823,148 @@ impl Stage {
             BufferSource::empty::<Vec3>(512),
         );
 
-        let bindings = Bindings {
-            vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer],
-            index_buffer: index_buffer,
-            images: vec![],
-        };
+        let normal_vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&cube_arrays.vert_normal),
+        );
 
-        let shader = ctx.new_shader(
-            ShaderSource::Glsl {
-                vertex: shader::VERTEX,
-                fragment: shader::FRAGMENT,
-            }, 
-            shader::meta()
-        ).unwrap();
+        // Bound whenever there's no texture, so the sampler always has something to read
+        let blank_texture = ctx.new_texture_from_rgba8(1, 1, &[255, 255, 255, 255]);
 
-        let pipeline = ctx.new_pipeline(
-            &[
-                BufferLayout::default(),
-                BufferLayout {
-                    step_func: VertexStep::PerInstance,
-                    ..Default::default()
+        let sphere_arrays = SphereArrays::new(SPHERE_SUBDIVISIONS);
+        let sphere_vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&sphere_arrays.vert_pos),
+        );
+        let sphere_index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&sphere_arrays.indices),
+        );
+        // A unit sphere's normals are its positions, doubled from the 0.5 radius
+        let sphere_normals: Vec<f32> = sphere_arrays.vert_pos.iter().map(|v| v * 2.0).collect();
+        let sphere_normal_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&sphere_normals),
+        );
+
+        let shadow_map = ShadowMap::new(&mut *ctx, 2, blank_texture);
+        let images = vec![blank_texture, shadow_map.depth];
+
+        let meshes = [
+            Mesh {
+                bindings: Bindings {
+                    vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer, normal_vertex_buffer],
+                    index_buffer,
+                    images: images.clone(),
                 },
-            ],
-            &[
-                VertexAttribute::with_buffer("position", VertexFormat::Float3, 0),
-                VertexAttribute::with_buffer("in_inst_pos", VertexFormat::Float3, 1),
-            ],
-            shader,
-            PipelineParams {
-                depth_test: Comparison::LessOrEqual,
-                depth_write: true,
-                ..Default::default()
+                index_count: cube_arrays.indices.len() as i32
+            },
+            Mesh {
+                bindings: Bindings {
+                    vertex_buffers: vec![sphere_vertex_buffer, positions_vertex_buffer, sphere_normal_buffer],
+                    index_buffer: sphere_index_buffer,
+                    images,
+                },
+                index_count: sphere_arrays.indices.len() as i32
             }
-        );
+        ];
+
+        let external_pipelines = shader::load_external()
+            .and_then(|(vertex, fragment)| {
+                CubePipelines::new(&mut *ctx, &vertex, &fragment)
+                    .map_err(|e| eprintln!("Using the built-in shaders, the external ones failed to compile: {}", e))
+                    .ok()
+            });
+        let pipelines = match external_pipelines {
+            Some(pipelines) => pipelines,
+            None => CubePipelines::new(&mut *ctx, shader::VERTEX, shader::FRAGMENT)?
+        };
+
+        let line_renderer = LineRenderer::new(&mut *ctx)?;
+        let point_renderer = PointRenderer::new(&mut *ctx)?;
+        let capture = args.render_to.clone()
+            .map(|path| Capture::new(&mut *ctx, (args.width as u32, args.height as u32), path, args.render_steps));
+        let screen_space_outlines = ScreenSpaceOutlines::new(&mut *ctx)?;
+        let trails = Trails::new(&mut *ctx)?;
+
+        let mut game_state = GameState::new_with_subdivision(args.subdivide);
+        game_state.texture_path = texture_path_from_env();
+        game_state.seed = args.seed;
+        if let Some(fade) = args.trails {
+            game_state.trail_fade = fade;
+        }
+        game_state.explode_fuse = args.fuse;
+        game_state.srgb_output = args.srgb;
+        if let Some(amount) = args.edge_darkening {
+            game_state.edge_darkening = amount;
+        }
+        if args.idle_spin {
+            game_state.idle_spin = Some(cubes::game::IDLE_SPIN);
+        }
+        if let Some(path) = args.sim_log.as_ref() {
+            game_state.sim_logger = SimLogger::create(path)
+                .map_err(|e| eprintln!("Could not create the simulation log {}: {}", path, e))
+                .ok();
+        }
 
-        Stage {
+        Ok(Stage {
             ctx,
-            pipeline,
-            bindings,
+            pipelines,
+            meshes,
+            line_renderer,
+            point_renderer,
+            debug_lines: DebugLines::new(),
+            screen_space_outlines,
+            trails,
+            shadow_map,
+            blank_texture,
+            texture: None,
+            clock: Box::new(SystemClock::new()),
+            timestep: FixedTimestep::new(GameState::steps_per_second()),
             input: GameInput::new(),
+            presses: VecDeque::new(),
             drag_last: None,
-            game_state: GameState::new(),
-            game_step_result: None
+            drag_delta: (0.0, 0.0),
+            zoom_keys: (false, false),
+            reticle_keys: [false; 4],
+            last_left_click: None,
+            shift_held: false,
+            game_state,
+            game_step_result: None,
+            clicked_ray: None,
+            ray_clicked: false,
+            demo: args.demo.then(DemoScript::reel),
+            attract: args.attract.map(AttractMode::new),
+            quick_save: None,
+            fullscreen: args.fullscreen,
+            trace: args.verbose.then(Default::default),
+            frame_stats: FrameStats::new(FRAME_STATS_WINDOW),
+            stats_printed_at: args.verbose.then_some(0.0),
+            quality: args.frame_budget.map(|ms| AdaptiveQuality::new(ms / 1000.0)),
+            capture,
+
+            #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+            shader_watcher: shader::Watcher::new()
+        })
+    }
+
+    /// The size of what's drawn to: the capture target if there is one, otherwise the window.
+    fn output_size(&self) -> (f32, f32) {
+        match self.capture {
+            Some(ref capture) => (capture.size.0 as f32, capture.size.1 as f32),
+            None => window::screen_size()
+        }
+    }
+
+    /// Begin a pass on the window, or on the capture target when rendering to a file.
+    fn begin_output_pass(ctx: &mut dyn RenderingBackend, capture: Option<&Capture>, action: PassAction) {
+        match capture {
+            Some(capture) => ctx.begin_pass(Some(capture.pass), action),
+            None => ctx.begin_default_pass(action)
         }
     }
 
@@ -104,142 +973,844 @@ impl Stage {
         let (width, height) = window::screen_size();
         let x = (x/width)*2.0 - 1.0;
         let y = -((y/height)*2.0 - 1.0);
-        return (x, y)
+        (x, y)
+    }
+
+    /// Swap in the external shaders if they changed and still compile.
+    #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+    fn reload_changed_shaders(&mut self) {
+        let now = self.clock.now();
+        let Some((vertex, fragment)) = self.shader_watcher.as_mut().and_then(|w| w.poll(now)) else {
+            return;
+        };
+
+        match CubePipelines::new(&mut *self.ctx, &vertex, &fragment) {
+            Ok(pipelines) => {
+                println!("Reloaded shaders");
+                let old = std::mem::replace(&mut self.pipelines, pipelines);
+                old.delete(&mut *self.ctx);
+            },
+            Err(e) => eprintln!("Keeping the previous shaders, the changed ones failed to compile: {}", e)
+        }
+    }
+
+    /// Load the texture in `GameState::texture_path` if it isn't already.
+    fn update_texture(&mut self) {
+        let path = self.game_state.texture_path.as_ref();
+        let mipmaps = self.game_state.texture_mipmaps;
+        if path == self.texture.as_ref().map(|t| &t.path) && self.texture.as_ref().is_none_or(|t| t.mipmaps == mipmaps) {
+            return;
+        }
+
+        if let Some(texture) = self.texture.take() {
+            self.ctx.delete_texture(texture.id);
+        }
+
+        if let Some(path) = path {
+            match load_texture(&mut *self.ctx, path, mipmaps) {
+                Ok(texture) => self.texture = Some(texture),
+                Err(e) => {
+                    eprintln!("Could not load the texture {}: {}", path.display(), e);
+                    // Don't try again until the path changes
+                    self.game_state.texture_path = None;
+                }
+            }
+        }
+
+        let image = match self.texture {
+            Some(ref texture) => texture.id,
+            None => self.blank_texture
+        };
+        for mesh in self.meshes.iter_mut() {
+            mesh.bindings.images[0] = image;
+        }
+    }
+
+    /// Called for every input event, so the demo script and attract mode get out of the way.
+    fn user_input(&mut self) {
+        if let Some(demo) = self.demo.as_mut() {
+            demo.interrupt();
+        }
+        if let Some(attract) = self.attract.as_mut() {
+            attract.interrupt();
+        }
+    }
+
+    /// Write out what's buffered of the simulation log, if there is one.
+    /// The stage isn't always dropped on the way out to do it then.
+    fn flush_sim_log(&mut self) {
+        if let Some(Err(e)) = self.game_state.sim_logger.as_mut().map(SimLogger::flush) {
+            eprintln!("Could not write the simulation log: {}", e);
+        }
+    }
+
+    /// Where an arrow key goes in `reticle_keys`.
+    fn reticle_key_index(keycode: KeyCode) -> Option<usize> {
+        match keycode {
+            KeyCode::Left => Some(0),
+            KeyCode::Right => Some(1),
+            KeyCode::Down => Some(2),
+            KeyCode::Up => Some(3),
+            _ => None
+        }
+    }
+
+    /// Build the input for the next step, handing it any pending presses.
+    fn next_step_input(&mut self) -> GameInput {
+        let mut input = self.input.clone();
+
+        // A held button only counts once its press has been seen by a step
+        if self.presses.contains(&Action::ExplodeSubcube) {
+            input.explode_subcube = false;
+        }
+
+        while let Some(&press) = self.presses.front() {
+            let flag = press.flag(&mut input);
+            if *flag {
+                // Pressed more than once; leave the rest for the following steps
+                break;
+            }
+            *flag = true;
+            self.presses.pop_front();
+        }
+
+        // Held zoom keys zoom by the same amount every step, regardless of frame rate
+        let (zoom_in, zoom_out) = self.zoom_keys;
+        let zoom_direction = zoom_in as i32 - zoom_out as i32;
+        input.zoom_view_change += zoom_direction as f32 * KEY_ZOOM_RATE / GameState::steps_per_second() as f32;
+
+        let [left, right, down, up] = self.reticle_keys.map(|held| held as i32 as f32);
+        let reticle_step = RETICLE_SPEED / GameState::steps_per_second() as f32;
+        input.move_reticle = ((right - left) * reticle_step, (up - down) * reticle_step);
+
+        // Changes accumulated since the last step are used up
+        self.input.zoom_view_change = 0.0;
+        self.input.fov_change = 0.0;
+
+        input
     }
 }
 
 impl EventHandler for Stage {
     fn update(&mut self) {
-        let (width, height) = window::screen_size();
-        let result = self.game_state.step((width as i32, height as i32), &self.input);
-        self.game_step_result = Some(result);
+        #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+        self.reload_changed_shaders();
 
-        self.input.rearrange = false;
-        self.input.hurl_all = false;
-        self.input.reset = false;
-        self.input.toggle_show_outlines = false;
-        self.input.zoom_view_change = 0.0;
+        let (width, height) = self.output_size();
+
+        // A captured frame must come out the same however long it takes to draw
+        let steps = match self.capture.as_mut() {
+            Some(capture) => std::mem::take(&mut capture.steps),
+            None => self.timestep.steps(&*self.clock)
+        };
+        if self.trace.is_some() && self.timestep.dropped() > 0.0 {
+            println!("Fell behind, skipped {:.2} seconds", self.timestep.dropped());
+        }
+        self.frame_stats.frame(&*self.clock);
+        if let Some(printed_at) = self.stats_printed_at.as_mut() {
+            let now = self.clock.now();
+            if now - *printed_at >= FRAME_STATS_INTERVAL {
+                *printed_at = now;
+                let ms = |p| self.frame_stats.percentile(p).unwrap_or(0.0) * 1000.0;
+                println!("Frame times: p50 {:.1} ms, p95 {:.1} ms, p99 {:.1} ms", ms(50.0), ms(95.0), ms(99.0));
+            }
+        }
+        if let Some(quality) = self.quality.as_mut() {
+            // Too few frames, and one slow startup frame would make up the whole 95th percentile
+            let p95 = self.frame_stats.percentile(95.0).filter(|_| self.frame_stats.len() >= FRAME_STATS_WINDOW / 10);
+            let changed = quality.update(p95, &mut self.game_state);
+            if changed && self.trace.is_some() {
+                match quality.is_limiting() {
+                    true => println!("Frames are over budget, exploding more coarsely"),
+                    false => println!("Frames are back in budget, exploding normally")
+                }
+            }
+        }
+
+        if steps > 0 {
+            self.input.rotate_view = drag_rotation(std::mem::take(&mut self.drag_delta), steps);
+        }
+
+        for _ in 0..steps {
+            let input = self.next_step_input();
+            let viewport = (width as i32, height as i32);
+            // While an interrupted demo script is paused, attract mode waits
+            // with it rather than taking over from the person using it
+            let scripted = match self.demo.as_mut() {
+                Some(demo) => demo.next_input(),
+                None => self.attract.as_mut().and_then(|attract| attract.next_input(&self.game_state, viewport))
+            };
+            let input = scripted.unwrap_or(input);
+            let result = self.game_state.step(viewport, &input);
+
+            if std::mem::take(&mut self.ray_clicked) {
+                if let Some(json) = result.debug_ray_json() {
+                    println!("{}", json);
+                    self.clicked_ray = result.debug_ray.map(|ray| (ray, result.debug_hit));
+                }
+            }
+            if !self.game_state.debug_picking {
+                self.clicked_ray = None;
+            }
+
+            // Where the click's explosion went off, for a second click to focus on
+            if let Some((_, exploded_at)) = self.last_left_click.as_mut().filter(|(_, pos)| pos.is_none()) {
+                *exploded_at = result.events.iter().map(|event| match *event {
+                    GameEvent::SubcubeExploded { pos, .. } => pos
+                }).next();
+            }
+
+            self.game_step_result = Some(result);
+
+            if let Some(e) = self.game_state.sim_logger.as_ref().and_then(SimLogger::error) {
+                eprintln!("Stopped logging the simulation: {}", e);
+                self.game_state.sim_logger = None;
+            }
+
+            // Checked every step, since the Resetting state only lasts for one
+            if let Some((subcube_count, state)) = self.trace.as_mut() {
+                if let Some(count) = subcube_count.set(self.game_state.cube.subcubes.len()) {
+                    println!("Subcubes: {}", count);
+                }
+                if let Some(state) = state.set(self.game_state.cube.state_kind()) {
+                    println!("Cube state: {:?}", state);
+                }
+            }
+        }
     }
     fn draw(&mut self) {
-        let Some(result) = std::mem::replace(&mut self.game_step_result, None) else {
+        self.update_texture();
+
+        let Some(result) = &self.game_step_result else {
             return;
         };
 
-        self.ctx.buffer_update(
-            self.bindings.vertex_buffers[1],
-            BufferSource::slice(&[0.0, 0.0, 0.0]),
-        );
+        self.ctx.buffer_update(
+            self.meshes[0].bindings.vertex_buffers[1],
+            BufferSource::slice(&[0.0, 0.0, 0.0]),
+        );
+
+        let projection_view = cgmath_to_glam(result.projection_view);
+        let outline_mode = self.game_state.outline_mode;
+        let show_outlines = outline_mode == OutlineMode::Geometry;
+        let shading_mode = self.game_state.shading_mode;
+        let textured = if self.texture.is_some() { 1 } else { 0 };
+        let shadow_mapped = self.game_state.shadow_mode == ShadowMode::Mapped;
+        let up_rotation = self.game_state.up_rotation();
+        let light_projection_view = ShadowMap::projection_view(up_rotation);
+        let edge_darkening = self.game_state.edge_darkening;
+        let inner_color = self.game_state.inner_color;
+        let drilling = self.input.explode_subcube;
+        let selected = self.game_state.selected();
+        let srgb_output = self.game_state.srgb_output;
+
+        // Opaque subcubes go first and fill the depth buffer. Translucent
+        // ones are then blended over them from back to front.
+        let (opaque, mut translucent): (Vec<_>, Vec<_>) = self.game_state.cube.iter_render()
+            .partition(|subcube| subcube.alpha >= 1.0);
+        if self.game_state.show_ghost {
+            let cube = &self.game_state.cube;
+            translucent.extend(cube.iter_rest_poses().zip(cube.iter_render()).map(|(model, subcube)| SubcubeRender {
+                model,
+                pos: cube.subcubes[subcube.index].segment,
+                alpha: GHOST_ALPHA,
+                // It's where the subcube belongs, not the subcube itself
+                highlightable: false,
+                ..subcube
+            }));
+        }
+        let view_depth = |subcube: &SubcubeRender| (result.projection_view * subcube.pos.extend(1.0)).w;
+        translucent.sort_by(|a, b| view_depth(b).total_cmp(&view_depth(a)));
+
+        // Each subcube flattened onto the floor. Only the faces turned toward the
+        // light survive back-face culling, so each shadow is covered once.
+        let mut shadows = Vec::new();
+        if self.game_state.shadow_mode == ShadowMode::Blob {
+            // The floor is SHADOW_FLOOR_Y along the up axis, so the light
+            // and floor turn with it
+            let up = self.game_state.up_axis();
+            let light = up_rotation * cgmath::Vector3::from(SHADOW_LIGHT_POS);
+            let light_height = light.dot(up);
+            let shadow_matrix = cgmath::Matrix4::identity()
+                .project_to_plane(&up, -SHADOW_FLOOR_Y, &light);
+            shadows.extend(self.game_state.cube.iter_render()
+                .filter(|subcube| subcube.pos.dot(up) > SHADOW_FLOOR_Y && subcube.pos.dot(up) < light_height)
+                .map(|subcube| SubcubeRender {
+                    model: shadow_matrix * subcube.model,
+                    alpha: SHADOW_ALPHA * subcube.alpha,
+                    ..subcube
+                }));
+        }
+
+        let opaque_blend = match self.texture {
+            Some(ref texture) if texture.has_alpha => Blend::Alpha,
+            _ => Blend::Opaque
+        };
+
+        // Looked up for every subcube drawn, so gather the soonest fuse on each once
+        let mut fuses = std::collections::HashMap::new();
+        for (index, remaining) in self.game_state.cube.fuses() {
+            let soonest = fuses.entry(index).or_insert(remaining);
+            *soonest = f32::min(*soonest, remaining);
+        }
+
+        let uniforms = |subcube: &SubcubeRender, projection_view, shadow: bool| {
+            let v = subcube.color_coord;
+
+            let fuse = fuses.get(&subcube.index).copied();
+            let highlight = match result.selected_subcube == Some(subcube.index) {
+                _ if !subcube.highlightable => Highlight::None,
+                _ if fuse.is_some_and(fuse_flash_on) => Highlight::Armed,
+                true if drilling => Highlight::Drilling,
+                true => Highlight::Hovered,
+                false if selected == Some(subcube.index) => Highlight::Selected,
+                false => Highlight::None
+            };
+
+            let shade_value = match shading_mode {
+                ShadingMode::Normal => 0.0,
+                ShadingMode::Depth => subcube.pos.magnitude() / DEPTH_SHADE_MAX,
+                ShadingMode::Speed => subcube.speed / SPEED_SHADE_MAX
+            };
+
+            shader::Uniforms {
+                projection_view,
+                model: cgmath_to_glam(subcube.model),
+                // Sphere edges aren't where a cube's would be
+                show_outlines: (show_outlines && subcube.shape == SubcubeShape::Cube) as u32,
+                highlight: highlight as i32,
+                cube_pos: glam::Vec3::new(v.x, v.y, v.z),
+                cube_size: subcube.subcube_length,
+                shading_mode: shading_mode as i32,
+                shade_value,
+                textured,
+                alpha: subcube.alpha,
+                shadow: shadow as i32,
+                light_projection_view,
+                shadow_mapped: shadow_mapped as i32,
+                has_color: subcube.color.is_some() as i32,
+                color: subcube.color.map_or(Vec3::ZERO, |c| Vec3::new(c.x, c.y, c.z)),
+                edge_darkening: if subcube.shape == SubcubeShape::Cube { edge_darkening } else { 0.0 },
+                outer_faces: subcube.outer_faces as i32,
+                // Spheres have no faces to tell apart
+                has_inner_color: (inner_color.is_some() && subcube.shape == SubcubeShape::Cube) as i32,
+                inner_color: inner_color.map_or(Vec3::ZERO, |c| Vec3::new(c.x, c.y, c.z)),
+                srgb_output: srgb_output as i32
+            }
+        };
+
+        if shadow_mapped {
+            // Only opaque subcubes cast shadows. Culling is left off so
+            // subcubes sliced open by the light's near plane still cast them.
+            self.ctx.begin_pass(Some(self.shadow_map.pass), Default::default());
+            self.ctx.apply_pipeline(self.pipelines.get(false, Blend::Opaque));
+            for (shape, mesh) in [SubcubeShape::Cube, SubcubeShape::Sphere].into_iter().zip(self.meshes.iter()) {
+                self.ctx.apply_bindings(&Bindings { images: self.shadow_map.images.clone(), ..mesh.bindings.clone() });
+                for subcube in opaque.iter().filter(|subcube| subcube.shape == shape) {
+                    let uniforms = shader::Uniforms { shadow_mapped: 0, ..uniforms(subcube, light_projection_view, false) };
+                    self.ctx.apply_uniforms(UniformsSource::table(&uniforms));
+                    self.ctx.draw(0, mesh.index_count, 1);
+                }
+            }
+            self.ctx.end_render_pass();
+        }
+
+        // Screen-space outlines draw their result straight to the output, so
+        // there's nothing left for trails to build on while they're on
+        let trails = self.game_state.trail_fade < 1.0 && outline_mode != OutlineMode::ScreenSpace;
+        if !trails {
+            self.trails.clear();
+        }
+
+        if outline_mode == OutlineMode::ScreenSpace {
+            let (width, height) = self.output_size();
+            let pass = self.screen_space_outlines.pass(&mut *self.ctx, (width as u32, height as u32));
+            self.ctx.begin_pass(Some(pass), Default::default());
+        } else if trails {
+            let (width, height) = self.output_size();
+            let pass = self.trails.pass(&mut *self.ctx, (width as u32, height as u32));
+            self.ctx.begin_pass(Some(pass), Default::default());
+        } else {
+            Stage::begin_output_pass(&mut *self.ctx, self.capture.as_ref(), Default::default());
+        }
+        let background = if srgb_output { linear_to_srgb(BACKGROUND) } else { BACKGROUND };
+        self.ctx.clear(Some((background[0], background[1], background[2], 1.)), None, None);
+        if trails {
+            self.trails.draw_previous(&mut *self.ctx, 1.0 - self.game_state.trail_fade);
+        }
+
+        if self.game_state.show_indices {
+            // Billboards: the digits lie in the screen's plane, just in front of each subcube
+            let (forward, up) = (self.game_state.camera_forward(), self.game_state.camera_up());
+            let right = forward.cross(up);
+            for subcube in self.game_state.cube.iter_render() {
+                let size = subcube.subcube_length * INDEX_LABEL_SIZE;
+                let center = cgmath::Point3::from_vec(subcube.pos - forward * subcube.subcube_length * INDEX_LABEL_OFFSET);
+                self.debug_lines.number(subcube.index, center, right * (size / 2.0), up * size, [1.0; 4]);
+            }
+        }
 
-        let projection_view = cgmath_to_glam(result.projection_view);
-        let show_outlines = if self.game_state.show_outlines { 1 } else { 0 };
-
-        self.ctx.begin_default_pass(Default::default());
-        self.ctx.clear(Some((0., 0., 0.25, 1.)), None, None);
-        self.ctx.apply_pipeline(&self.pipeline);
-        self.ctx.apply_bindings(&self.bindings);
-
-        let mut idx = 0;
-        for subcube in &self.game_state.cube.subcubes {
-            let l = 0.5 - subcube.subcube_length / 2.0;
-            let pos = match subcube.segment {
-                v => (v.x + l, v.y + l, v.z + l)
-            };
-            let pos = glam::Vec3::new(pos.0, pos.1, pos.2);
+        if let Some((point, _)) = self.game_state.magnet {
+            self.debug_lines.cross(cgmath::Point3::from_vec(point), DEBUG_MARKER_SIZE, [0.4, 0.8, 1.0, 1.0]);
+        }
 
-            let model = cgmath_to_glam(subcube.get_model_matrix());
+        if let Some((ray, hit)) = self.clicked_ray {
+            self.debug_lines.push(ray.origin, ray.origin + ray.direction * DEBUG_RAY_LENGTH, [1.0, 0.0, 0.0, 1.0]);
+            if let Some(hit) = hit {
+                self.debug_lines.cross(hit, DEBUG_MARKER_SIZE, [1.0, 1.0, 0.0, 1.0]);
+            }
+        }
 
-            let hovered = match result.selected_subcube {
-                Some(selected_idx) => idx == selected_idx,
-                None => false
-            };
-            let hovered = if hovered { 1 } else { 0 };
-
-            self.ctx.apply_uniforms(UniformsSource::table(&shader::Uniforms {
-                projection_view: projection_view,
-                model: model,
-                show_outlines: show_outlines,
-                hovered: hovered,
-                cube_pos: pos,
-                cube_size: subcube.subcube_length
-            }));
-            self.ctx.draw(0, 36, 1);
+        let passes = [
+            (opaque, opaque_blend, false),
+            (shadows, Blend::Translucent, true),
+            (translucent, Blend::Translucent, false)
+        ];
+        if self.game_state.render_style == RenderStyle::Points {
+            let (_, height) = self.output_size();
+            let point_scale = height / 2.0 * self.game_state.screen_scale();
+            let subcubes: Vec<_> = self.game_state.cube.iter_render().collect();
+            self.point_renderer.draw(&mut *self.ctx, projection_view, point_scale, srgb_output, &subcubes);
+        }
+        for (subcubes, blend, shadow) in passes {
+            if subcubes.is_empty() || self.game_state.render_style != RenderStyle::Solid {
+                continue;
+            }
+            let cull_faces = shadow || self.game_state.cull_faces;
+            self.ctx.apply_pipeline(self.pipelines.get(cull_faces, blend));
+
+            // Translucent subcubes must stay in order, so rebind whenever the shape changes
+            let mut bound = None;
+            for subcube in &subcubes {
+                let mesh = &self.meshes[subcube.shape as usize];
+                if bound != Some(subcube.shape) {
+                    self.ctx.apply_bindings(&mesh.bindings);
+                    bound = Some(subcube.shape);
+                }
+                self.ctx.apply_uniforms(UniformsSource::table(&uniforms(subcube, projection_view, shadow)));
+                self.ctx.draw(0, mesh.index_count, 1);
+            }
+        }
 
-            idx += 1;
+        if trails {
+            // The labels and picking ray go over the finished frame instead,
+            // so they don't smear. The scene's depth stays with the trails,
+            // so clear whatever's left from the last frame and they're never hidden.
+            self.ctx.end_render_pass();
+            let clear_depth = PassAction::Clear { color: None, depth: Some(1.0), stencil: None };
+            Stage::begin_output_pass(&mut *self.ctx, self.capture.as_ref(), clear_depth);
+            self.trails.draw_finished(&mut *self.ctx);
         }
+        self.line_renderer.flush(&mut *self.ctx, projection_view, srgb_output, &mut self.debug_lines);
 
         self.ctx.end_render_pass();
 
+        if outline_mode == OutlineMode::ScreenSpace {
+            Stage::begin_output_pass(&mut *self.ctx, self.capture.as_ref(), PassAction::Nothing);
+            let orthographic = self.game_state.is_orthographic();
+            self.screen_space_outlines.draw(&mut *self.ctx, self.game_state.projection_clip_planes(), orthographic);
+            self.ctx.end_render_pass();
+        }
+
+        if let Some((x, y)) = self.game_state.reticle {
+            // A crosshair drawn straight in normalized device coordinates, over everything
+            let (width, height) = self.output_size();
+            let (dx, dy) = (RETICLE_SIZE * 2.0 / width, RETICLE_SIZE * 2.0 / height);
+            let mut crosshair = DebugLines::new();
+            let white = [1.0; 4];
+            crosshair.push(cgmath::Point3::new(x - dx, y, 0.0), cgmath::Point3::new(x + dx, y, 0.0), white);
+            crosshair.push(cgmath::Point3::new(x, y - dy, 0.0), cgmath::Point3::new(x, y + dy, 0.0), white);
+            // Clearing the depth keeps subcubes from hiding it
+            let clear_depth = PassAction::Clear { color: None, depth: Some(1.0), stencil: None };
+            Stage::begin_output_pass(&mut *self.ctx, self.capture.as_ref(), clear_depth);
+            self.line_renderer.flush(&mut *self.ctx, Mat4::IDENTITY, srgb_output, &mut crosshair);
+            self.ctx.end_render_pass();
+        }
+
         self.ctx.commit_frame();
+
+        if let Some(capture) = self.capture.take() {
+            if let Err(e) = capture.save(&mut *self.ctx) {
+                eprintln!("Could not save the frame: {}", e);
+                EXIT_CODE.store(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            // Ordering a quit skips `quit_requested_event`
+            self.flush_sim_log();
+            window::order_quit();
+        }
+    }
+    fn window_minimized_event(&mut self) {
+        // Keys let go of while minimized never send their key up, so
+        // forget them rather than have them stick
+        self.shift_held = false;
+        self.zoom_keys = (false, false);
+        self.reticle_keys = [false; 4];
+        self.input.magnet = false;
+    }
+    fn quit_requested_event(&mut self) {
+        self.flush_sim_log();
     }
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.user_input();
         let (x, y) = Stage::window_to_ndc_coordinates(x, y);
         self.input.pointer = Some((x, y));
 
         if let Some((lastx, lasty)) = self.drag_last {
-            self.input.rotate_view = ((x - lastx) * DRAG_COEFF, (y - lasty) * DRAG_COEFF);
+            let (dx, dy) = aspect_corrected((x - lastx, y - lasty), window::screen_size());
+            self.drag_delta.0 += dx;
+            self.drag_delta.1 += dy;
             self.drag_last = Some((x, y));
-        } else {
-            self.input.rotate_view = (0.0, 0.0)
         }
     }
 
     fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
-        if button == MouseButton::Left {
-            self.input.explode_subcube = true;
+        self.user_input();
+        if button == MouseButton::Left && self.shift_held {
+            self.presses.push_back(Action::CrackSubcube);
+            self.ray_clicked = true;
+        } else if button == MouseButton::Left {
+            let now = self.clock.now();
+            match self.last_left_click.take() {
+                // The first click already blew apart what it picked, so the
+                // second focuses on where that was rather than exploding again
+                Some((at, exploded_at)) if now - at <= DOUBLE_CLICK_TIME => match exploded_at {
+                    Some(pos) => self.game_state.focus_on(pos),
+                    None => self.presses.push_back(Action::FocusSubcube)
+                },
+                _ => {
+                    self.input.explode_subcube = true;
+                    self.presses.push_back(Action::ExplodeSubcube);
+                    self.ray_clicked = true;
+                    self.last_left_click = Some((now, None));
+                }
+            }
         }
         if button == MouseButton::Right {
-            self.input.rearrange = true;
+            self.presses.push_back(Action::Rearrange);
         }
         if button == MouseButton::Middle {
-            self.input.rotate_view = (0.0, 0.0);
+            self.drag_delta = (0.0, 0.0);
             self.drag_last = Some(Stage::window_to_ndc_coordinates(x, y));
         }
     }
     fn mouse_button_up_event(&mut self, button: MouseButton, _x: f32, _y: f32) {
+        self.user_input();
         if button == MouseButton::Left {
             self.input.explode_subcube = false;
         }
         if button == MouseButton::Middle {
+            // Movement since the last steps still counts, so a flick keeps its speed
             self.drag_last = None;
+            self.presses.push_back(Action::ReleaseView);
         }
     }
     fn char_event(&mut self, c: char, _keymods: KeyMods, _repeat: bool) {
+        self.user_input();
         let c = c.to_ascii_lowercase();
         if c == ' ' {
-            self.input.hurl_all = true;
+            self.presses.push_back(Action::HurlAll);
         }
         if c == 'r' {
-            self.input.reset = true;
+            self.presses.push_back(Action::Reset);
+        }
+        if c == 'f' {
+            self.presses.push_back(Action::Freeze);
+        }
+        if c == 'c' {
+            self.presses.push_back(Action::ResetCamera);
         }
         if c == 'o' {
-            self.input.toggle_show_outlines = true;
+            self.presses.push_back(Action::CycleOutlineMode);
+        }
+        if c == 'b' {
+            self.presses.push_back(Action::ToggleCullFaces);
+        }
+        if c == 'i' {
+            self.presses.push_back(Action::ToggleOrbitInertia);
+        }
+        if c == 'g' {
+            self.presses.push_back(Action::ToggleShowGhost);
+        }
+        if c == 's' {
+            self.presses.push_back(Action::CycleShadowMode);
+        }
+        if c == 'p' {
+            self.presses.push_back(Action::ToggleDebugPicking);
+        }
+        if c == 'n' {
+            self.presses.push_back(Action::ToggleShowIndices);
+        }
+        if c == 'k' {
+            self.presses.push_back(Action::CyclePaletteMode);
+        }
+        if c == 'm' {
+            self.presses.push_back(Action::CycleShadingMode);
+        }
+        if c == 'v' {
+            self.presses.push_back(Action::CycleRenderStyle);
+        }
+        if c == 'l' {
+            self.presses.push_back(Action::ToggleProjection);
+        }
+        if c == 'e' {
+            self.presses.push_back(Action::ToggleExplodeShape);
+        }
+        if c == 'x' {
+            self.presses.push_back(Action::ToggleBounds);
+        }
+        if c == 't' {
+            self.presses.push_back(Action::ToggleReticle);
+        }
+        if c == '-' {
+            self.input.fov_change -= FOV_STEP;
+        }
+        if c == '=' {
+            self.input.fov_change += FOV_STEP;
+        }
+    }
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        self.user_input();
+        // Catches up on a shift released while another window had focus
+        self.shift_held = keymods.shift;
+        if let Some(i) = Stage::reticle_key_index(keycode) {
+            self.reticle_keys[i] = true;
+        }
+        if keycode == KeyCode::Enter && !repeat && self.game_state.reticle.is_some() {
+            // Explodes at the reticle like a left click
+            self.input.explode_subcube = true;
+            self.presses.push_back(Action::ExplodeSubcube);
+            self.ray_clicked = true;
+        }
+        if keycode == KeyCode::LeftShift || keycode == KeyCode::RightShift {
+            self.shift_held = true;
+        }
+        if keycode == KeyCode::A {
+            self.input.magnet = true;
+        }
+        if keycode == KeyCode::Tab {
+            self.presses.push_back(if self.shift_held { Action::SelectPrev } else { Action::SelectNext });
+        }
+        if keycode == KeyCode::Delete && !repeat {
+            self.presses.push_back(Action::ExplodeSelected);
+        }
+        if keycode == KeyCode::Home {
+            self.presses.push_back(Action::ResetPivot);
+        }
+        if keycode == KeyCode::F5 {
+            self.quick_save = Some(self.game_state.save());
+        }
+        if keycode == KeyCode::F9 {
+            if let Some(save) = self.quick_save.clone() {
+                self.game_state.load(save);
+            }
+        }
+        if keycode == KeyCode::F11 {
+            // The projection and pointer math read the window size every time,
+            // so they follow the change without any extra work
+            self.fullscreen = !self.fullscreen;
+            window::set_fullscreen(self.fullscreen);
+        }
+        if keycode == KeyCode::PageUp {
+            self.zoom_keys.0 = true;
+        }
+        if keycode == KeyCode::PageDown {
+            self.zoom_keys.1 = true;
+        }
+    }
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        self.user_input();
+        self.shift_held = keymods.shift;
+        if let Some(i) = Stage::reticle_key_index(keycode) {
+            self.reticle_keys[i] = false;
+        }
+        if keycode == KeyCode::Enter {
+            self.input.explode_subcube = false;
+        }
+        if keycode == KeyCode::A {
+            self.input.magnet = false;
+        }
+        if keycode == KeyCode::LeftShift || keycode == KeyCode::RightShift {
+            self.shift_held = false;
+        }
+        if keycode == KeyCode::PageUp {
+            self.zoom_keys.0 = false;
+        }
+        if keycode == KeyCode::PageDown {
+            self.zoom_keys.1 = false;
         }
     }
     fn mouse_wheel_event(&mut self, _x: f32, y: f32) {
+        self.user_input();
         // +Y zooms in, -Y zooms out
-        self.input.zoom_view_change = (y as f32) * ZOOM_COEFF;
+        self.input.zoom_view_change += y * ZOOM_COEFF;
+    }
+}
+
+/// Whether buffer swaps wait for the display's vertical blank.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum VSyncMode {
+    Off,
+    On,
+    /// Wait, unless the frame is already late, in which case swap immediately
+    Adaptive
+}
+
+impl VSyncMode {
+    fn swap_interval(self) -> i32 {
+        match self {
+            VSyncMode::Off => 0,
+            VSyncMode::On => 1,
+            VSyncMode::Adaptive => -1
+        }
+    }
+}
+
+impl std::str::FromStr for VSyncMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<VSyncMode, String> {
+        match s {
+            "off" => Ok(VSyncMode::Off),
+            "on" => Ok(VSyncMode::On),
+            "adaptive" => Ok(VSyncMode::Adaptive),
+            _ => Err(format!("Unknown vsync mode {:?}, expected off, on or adaptive", s))
+        }
+    }
+}
+
+/// Parse the command line, exiting with the usage on a bad argument.
+#[cfg(not(target_arch = "wasm32"))]
+fn args_from_command_line() -> Args {
+    match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, args::USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// There's no command line in the browser.
+#[cfg(target_arch = "wasm32")]
+fn args_from_command_line() -> Args {
+    Args::default()
+}
+
+/// Read `CUBES_VSYNC`. Returns None to leave it up to the driver.
+#[cfg(not(target_arch = "wasm32"))]
+fn vsync_from_env() -> Option<VSyncMode> {
+    let value = std::env::var("CUBES_VSYNC").ok()?;
+    value.parse().map_err(|e| eprintln!("{}", e)).ok()
+}
+
+/// The browser always paces frames to the display.
+#[cfg(target_arch = "wasm32")]
+fn vsync_from_env() -> Option<VSyncMode> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn texture_path_from_env() -> Option<std::path::PathBuf> {
+    std::env::var_os("CUBES_TEXTURE").map(Into::into)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn texture_path_from_env() -> Option<std::path::PathBuf> {
+    None
+}
+
+/// Decode a PNG file and upload it as a texture.
+///
+/// Non-power-of-two mipmaps need GL 3.0 or GLES 3.0; WebGL 1 can't sample them,
+/// which doesn't matter yet because textures aren't loaded on wasm.
+fn load_texture(ctx: &mut dyn RenderingBackend, path: &std::path::Path, mipmaps: bool) -> Result<LoadedTexture, String> {
+    let texture = Texture2D::from_file(path.to_str().ok_or("The path isn't valid UTF-8")?)?;
+
+    let id = ctx.new_texture_from_data_and_format(&texture.rgba, TextureParams {
+        width: texture.width,
+        height: texture.height,
+        format: TextureFormat::RGBA8,
+        // Face coordinates never leave 0..1, so don't bleed in the opposite edge
+        wrap: TextureWrap::Clamp,
+        min_filter: FilterMode::Linear,
+        mag_filter: FilterMode::Linear,
+        mipmap_filter: if mipmaps { MipmapFilterMode::Linear } else { MipmapFilterMode::None },
+        allocate_mipmaps: mipmaps,
+        ..Default::default()
+    });
+    if mipmaps {
+        ctx.texture_generate_mipmaps(id);
     }
+
+    Ok(LoadedTexture {
+        path: path.to_path_buf(),
+        id,
+        has_alpha: texture.has_alpha,
+        mipmaps
+    })
 }
 
 fn cgmath_to_glam(mat: cgmath::Matrix4<f32>) -> Mat4 {
-    use cgmath::Matrix;
-    let ptr = mat.as_ptr();
-    let arr: &[f32; 16] = unsafe { std::mem::transmute(ptr) };
-    Mat4::from_cols_array(arr)
+    // Both are column-major
+    let cols: [[f32; 4]; 4] = mat.into();
+    Mat4::from_cols_array_2d(&cols)
+}
+
+/// Scale a movement in pointer coordinates so both axes are in units of the
+/// window's height. Otherwise the same distance across a wide window would
+/// count for less than it does vertically.
+fn aspect_corrected((dx, dy): (f32, f32), (width, height): (f32, f32)) -> (f32, f32) {
+    (dx * width / height, dy)
+}
+
+/// The `GameInput::rotate_view` for each of `steps` steps, to rotate by a drag of `delta` between them.
+fn drag_rotation((dx, dy): (f32, f32), steps: u32) -> (f32, f32) {
+    let scale = DRAG_COEFF / steps as f32;
+    (dx * scale, dy * scale)
 }
 
 fn main() {
+    let args = args_from_command_line();
+    if args.help {
+        println!("{}", args::USAGE);
+        return;
+    }
+    if let Some(seed) = args.seed {
+        quad_rand::srand(seed);
+    }
+
     let conf = conf::Conf {
         window_title: "Rust Cubes Demo".to_string(),
-        window_width: 1920,
-        window_height: 1080,
+        window_width: args.width,
+        window_height: args.height,
+        fullscreen: args.fullscreen,
+        platform: conf::Platform {
+            swap_interval: args.vsync.or_else(vsync_from_env).map(VSyncMode::swap_interval),
+            ..Default::default()
+        },
         ..Default::default()
     };
-    miniquad::start(conf, move || Box::new(Stage::new()));
+    miniquad::start(conf, move || {
+        match Stage::new(&args) {
+            Ok(stage) => Box::new(stage),
+            Err(e) => {
+                eprintln!("Could not set up the renderer: {}", e);
+                std::process::exit(1);
+            }
+        }
+    });
+
+    // Only reached on platforms where `start` returns once the window closes
+    match EXIT_CODE.load(std::sync::atomic::Ordering::Relaxed) {
+        0 => (),
+        code => std::process::exit(code)
+    }
 }
 
 struct CubeArrays {
     pub vert_pos: [f32; 6*4 * 3],
+    /// The outward direction of the face each vertex belongs to
+    pub vert_normal: [f32; 6*4 * 3],
     pub indices: [u8; 6*6]
 }
 
@@ -278,14 +1849,16 @@ impl CubeArrays {
             3,7,2,6
         ];
     
-        // Which vertices to form triangle faces from
+        // Which vertices to form triangle faces from.
+        // Triangles wind counter-clockwise when seen from outside the cube,
+        // matching the pipeline's front face order.
         static IDX: [u8; 6*6] = [
-            0,1,2, 1,3,2,
-            4,5,6, 5,7,6,
-            8,9,10, 9,11,10,
-            12,13,14, 13,15,14,
-            16,17,18, 17,19,18,
-            20,21,22, 21,23,22
+            0,2,1, 1,2,3,
+            4,6,5, 5,6,7,
+            8,10,9, 9,10,11,
+            12,14,13, 13,14,15,
+            16,18,17, 17,18,19,
+            20,22,21, 21,22,23
         ];
     
         let buffer: Vec<f32> = VERT_IDX.iter().flat_map(|&i| {
@@ -293,13 +1866,69 @@ impl CubeArrays {
             vec![x, y, z].into_iter()
         }).collect();
 
+        // The corners of each face add up to twice its normal
+        let normals: Vec<f32> = VERT_IDX.chunks(4).flat_map(|face| {
+            let n = face.iter().fold([0.0; 3], |n, &i| {
+                let (x,y,z) = corner[i];
+                [n[0] + x, n[1] + y, n[2] + z]
+            }).map(|v| v / (2.0 * length));
+            std::iter::repeat_n(n, 4).flatten()
+        }).collect();
+
         CubeArrays {
             vert_pos: buffer.try_into().unwrap(),
+            vert_normal: normals.try_into().unwrap(),
             indices: IDX
         }
     }
 }
 
+/// An icosphere of radius 0.5, to fit inside the same bounds as `CubeArrays`.
+struct SphereArrays {
+    pub vert_pos: Vec<f32>,
+    pub indices: Vec<u16>
+}
+
+impl SphereArrays {
+    pub fn new(subdivisions: u32) -> SphereArrays {
+        use std::collections::HashMap;
+
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut verts: Vec<Vec3> = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0)
+        ].iter().map(|&(x, y, z)| Vec3::new(x, y, z).normalize() * 0.5).collect();
+
+        // Counter-clockwise when seen from outside, like CubeArrays
+        let mut faces: Vec<[u16; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]
+        ];
+
+        for _ in 0..subdivisions {
+            // Neighboring triangles share the midpoints of their shared edges
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u16, b: u16| *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                verts.push(((verts[a as usize] + verts[b as usize]) / 2.0).normalize() * 0.5);
+                (verts.len() - 1) as u16
+            });
+
+            faces = faces.iter().flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            }).collect();
+        }
+
+        SphereArrays {
+            vert_pos: verts.iter().flat_map(|v| v.to_array()).collect(),
+            indices: faces.into_iter().flatten().collect()
+        }
+    }
+}
+
 
 mod shader {
     use miniquad::*;
@@ -307,17 +1936,111 @@ mod shader {
     pub const VERTEX: &str = include_str!("shaders/vertex.glsl");
     pub const FRAGMENT: &str = include_str!("shaders/fragment.glsl");
 
+    #[cfg(not(target_arch = "wasm32"))]
+    const FILENAMES: [&str; 2] = ["vertex.glsl", "fragment.glsl"];
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn external_dir() -> Option<std::path::PathBuf> {
+        std::env::var_os("CUBES_SHADER_DIR").map(std::path::PathBuf::from)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_sources(dir: &std::path::Path) -> Option<(String, String)> {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            std::fs::read_to_string(&path)
+                .map_err(|e| eprintln!("Could not read {}: {}", path.display(), e))
+                .ok()
+        };
+
+        Some((read(FILENAMES[0])?, read(FILENAMES[1])?))
+    }
+
+    /// Read `vertex.glsl` and `fragment.glsl` from the directory in `CUBES_SHADER_DIR`.
+    /// Returns None if the variable isn't set or either file can't be read.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_external() -> Option<(String, String)> {
+        read_sources(&external_dir()?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_external() -> Option<(String, String)> {
+        None
+    }
+
+    /// Watches the external shaders for changes.
+    #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+    pub struct Watcher {
+        dir: std::path::PathBuf,
+        modified: Option<std::time::SystemTime>,
+        last_poll: f64
+    }
+
+    #[cfg(all(feature = "dev", not(target_arch = "wasm32")))]
+    impl Watcher {
+        // Seconds between checking the files
+        const POLL_INTERVAL: f64 = 0.5;
+
+        /// Returns None if `CUBES_SHADER_DIR` isn't set.
+        pub fn new() -> Option<Watcher> {
+            let dir = external_dir()?;
+            let modified = Watcher::last_modified(&dir);
+
+            Some(Watcher {
+                dir,
+                modified,
+                last_poll: 0.0
+            })
+        }
+
+        fn last_modified(dir: &std::path::Path) -> Option<std::time::SystemTime> {
+            FILENAMES.iter()
+                .filter_map(|name| std::fs::metadata(dir.join(name)).and_then(|m| m.modified()).ok())
+                .max()
+        }
+
+        /// Returns the new shader sources if either file changed since the last poll.
+        pub fn poll(&mut self, now: f64) -> Option<(String, String)> {
+            if now - self.last_poll < Watcher::POLL_INTERVAL {
+                return None;
+            }
+            self.last_poll = now;
+
+            let modified = Watcher::last_modified(&self.dir);
+            if modified == self.modified {
+                return None;
+            }
+            self.modified = modified;
+
+            read_sources(&self.dir)
+        }
+    }
+
     pub fn meta() -> ShaderMeta {
         ShaderMeta {
-            images: vec![],
+            images: vec!["tex".to_string(), "shadow_map".to_string()],
             uniforms: UniformBlockLayout {
                 uniforms: vec![
                     UniformDesc::new("projection_view", UniformType::Mat4),
                     UniformDesc::new("model", UniformType::Mat4),
                     UniformDesc::new("show_outlines", UniformType::Int1),
-                    UniformDesc::new("hovered", UniformType::Int1),
+                    UniformDesc::new("highlight", UniformType::Int1),
                     UniformDesc::new("cube_pos", UniformType::Float3),
                     UniformDesc::new("cube_size", UniformType::Float1),
+                    UniformDesc::new("shading_mode", UniformType::Int1),
+                    UniformDesc::new("shade_value", UniformType::Float1),
+                    UniformDesc::new("textured", UniformType::Int1),
+                    UniformDesc::new("alpha", UniformType::Float1),
+                    UniformDesc::new("shadow", UniformType::Int1),
+                    UniformDesc::new("light_projection_view", UniformType::Mat4),
+                    UniformDesc::new("shadow_mapped", UniformType::Int1),
+                    UniformDesc::new("has_color", UniformType::Int1),
+                    UniformDesc::new("color", UniformType::Float3),
+                    UniformDesc::new("edge_darkening", UniformType::Float1),
+                    UniformDesc::new("outer_faces", UniformType::Int1),
+                    UniformDesc::new("has_inner_color", UniformType::Int1),
+                    UniformDesc::new("inner_color", UniformType::Float3),
+                    UniformDesc::new("srgb_output", UniformType::Int1),
                 ],
             },
         }
@@ -328,9 +2051,211 @@ mod shader {
         pub projection_view: glam::Mat4,
         pub model: glam::Mat4,
         pub show_outlines: u32,
-        pub hovered: u32,
+        /// A `Highlight`
+        pub highlight: i32,
         pub cube_pos: glam::Vec3,
-        pub cube_size: f32
+        pub cube_size: f32,
+        pub shading_mode: i32,
+        /// The scalar fed through the colormap, from 0 to 1
+        pub shade_value: f32,
+        pub textured: i32,
+        pub alpha: f32,
+        /// Draw flat black at `alpha`, for drop shadows
+        pub shadow: i32,
+        pub light_projection_view: glam::Mat4,
+        /// Darken fragments the shadow map says are hidden from the light
+        pub shadow_mapped: i32,
+        /// If set, `color` replaces the color from the subcube's place in the cube
+        pub has_color: i32,
+        pub color: glam::Vec3,
+        pub edge_darkening: f32,
+        /// `Subcube::outer_faces`
+        pub outer_faces: i32,
+        /// If set, `inner_color` is drawn on the faces not in `outer_faces`
+        pub has_inner_color: i32,
+        pub inner_color: glam::Vec3,
+        /// Treat colors as linear and encode them as sRGB, as in `GameState::srgb_output`
+        pub srgb_output: i32
+    }
+
+}
+
+mod line_shader {
+    use miniquad::*;
+
+    pub const VERTEX: &str = include_str!("shaders/line_vertex.glsl");
+    pub const FRAGMENT: &str = include_str!("shaders/line_fragment.glsl");
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec![],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("projection_view", UniformType::Mat4),
+                    UniformDesc::new("srgb_output", UniformType::Int1),
+                ],
+            },
+        }
+    }
+
+    #[repr(C)]
+    pub struct Uniforms {
+        pub projection_view: glam::Mat4,
+        pub srgb_output: i32
+    }
+}
+
+mod point_shader {
+    use miniquad::*;
+
+    pub const VERTEX: &str = include_str!("shaders/point_vertex.glsl");
+    pub const FRAGMENT: &str = include_str!("shaders/point_fragment.glsl");
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec![],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("projection_view", UniformType::Mat4),
+                    UniformDesc::new("point_scale", UniformType::Float1),
+                    UniformDesc::new("srgb_output", UniformType::Int1),
+                ],
+            },
+        }
+    }
+
+    #[repr(C)]
+    pub struct Uniforms {
+        pub projection_view: glam::Mat4,
+        pub point_scale: f32,
+        pub srgb_output: i32
+    }
+}
+
+mod trail_shader {
+    use miniquad::*;
+
+    // The same fullscreen quad as the outlines
+    pub const VERTEX: &str = include_str!("shaders/outline_vertex.glsl");
+    pub const FRAGMENT: &str = include_str!("shaders/trail_fragment.glsl");
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["color_tex".to_string()],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("opacity", UniformType::Float1),
+                ],
+            },
+        }
+    }
+
+    #[repr(C)]
+    pub struct Uniforms {
+        pub opacity: f32
+    }
+}
+
+mod outline_shader {
+    use miniquad::*;
+
+    pub const VERTEX: &str = include_str!("shaders/outline_vertex.glsl");
+    pub const FRAGMENT: &str = include_str!("shaders/outline_fragment.glsl");
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["color_tex".to_string(), "depth_tex".to_string()],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("texel_size", UniformType::Float2),
+                    UniformDesc::new("near", UniformType::Float1),
+                    UniformDesc::new("far", UniformType::Float1),
+                    UniformDesc::new("orthographic", UniformType::Int1),
+                ],
+            },
+        }
+    }
+
+    #[repr(C)]
+    pub struct Uniforms {
+        pub texel_size: glam::Vec2,
+        pub near: f32,
+        pub far: f32,
+        pub orthographic: i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drag_rotation_follows_the_distance_dragged() {
+        // However the movement is split into events and steps, it rotates the same in total
+        let (x, y) = drag_rotation((0.1 + 0.2 + 0.3, -0.6), 3);
+        let (one_x, one_y) = drag_rotation((0.6, -0.6), 1);
+        assert!((x * 3.0 - one_x).abs() < 1e-6 && (y * 3.0 - one_y).abs() < 1e-6);
+        assert_eq!(drag_rotation((0.0, 0.0), 2), (0.0, 0.0));
+    }
+
+    #[test]
+    fn drags_count_the_same_on_both_axes() {
+        // 100 pixels right and 100 pixels up on a 1600x900 window
+        let size = (1600.0, 900.0);
+        let (dx, dy) = aspect_corrected((100.0 / 1600.0 * 2.0, 100.0 / 900.0 * 2.0), size);
+        assert!((dx - dy).abs() < 1e-6);
+        assert_eq!(aspect_corrected((0.5, 0.5), (600.0, 600.0)), (0.5, 0.5));
+    }
+
+    #[test]
+    fn points_are_colored_like_the_solids() {
+        let mut cube = cubes::game::cube::Cube::new();
+        let subcube = cube.iter_render().next().unwrap();
+        // The lone unit cube's coordinate is (0, 0, 0): no saturation, at the lowest value
+        assert!((subcube_rgb(&subcube) - Vec3::splat(0.25)).abs().max_element() < 1e-6);
+
+        cube.for_each_subcube_mut(|s| s.set_color(Some(cgmath::Vector3::new(1.0, 0.0, 0.5))));
+        let subcube = cube.iter_render().next().unwrap();
+        assert_eq!(subcube_rgb(&subcube), Vec3::new(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn vertices_are_packed_like_their_attributes() {
+        use std::mem::{offset_of, size_of};
+        // `BufferLayout::default()` reads each vertex's attributes back to back
+        assert_eq!(size_of::<LineVertex>(), 7 * 4);
+        assert_eq!(offset_of!(LineVertex, color), 3 * 4);
+        assert_eq!(size_of::<PointVertex>(), 8 * 4);
+        assert_eq!(offset_of!(PointVertex, size), 3 * 4);
+        assert_eq!(offset_of!(PointVertex, color), 4 * 4);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn srgb_encoding_lightens_midtones_and_keeps_the_ends() {
+        let [black, white, over] = linear_to_srgb([0.0, 1.0, 2.0]);
+        assert_eq!(black, 0.0);
+        assert!((white - 1.0).abs() < 1e-6 && (over - 1.0).abs() < 1e-6);
+        let [mid, _, _] = linear_to_srgb([0.214, 0.0, 0.0]);
+        assert!((mid - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn cgmath_to_glam_keeps_every_element() {
+        // Every element is distinct, so a transpose or shuffle would show
+        let mat = cgmath::Matrix4::new(
+            1.0, 2.0, 3.0, 4.0,
+            5.0, 6.0, 7.0, 8.0,
+            9.0, 10.0, 11.0, 12.0,
+            13.0, 14.0, 15.0, 16.0
+        );
+        let converted = cgmath_to_glam(mat);
+
+        for col in 0..4 {
+            for row in 0..4 {
+                assert_eq!(converted.col(col)[row], mat[col][row], "column {}, row {}", col, row);
+            }
+        }
+        // cgmath::Matrix4::new takes its arguments column by column
+        assert_eq!(converted.col(1), glam::Vec4::new(5.0, 6.0, 7.0, 8.0));
+    }
+}
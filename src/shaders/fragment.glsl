@@ -3,10 +3,33 @@ precision highp float;
 
 varying vec3 fragment_coord;
 varying vec3 npos;
+varying vec3 face_normal;
+varying vec4 light_space_pos;
 
 uniform bool show_outlines;
-uniform bool hovered;
+// 0: none, 1: hovered, 2: selected, 3: drilling, 4: armed
+uniform int highlight;
 uniform float cube_size;
+// 0: normal, 1: depth, 2: speed
+uniform int shading_mode;
+uniform float shade_value;
+uniform bool textured;
+uniform float alpha;
+uniform bool shadow;
+uniform bool shadow_mapped;
+uniform bool has_color;
+// How much to darken toward the edges of each face, from 0 to 1
+uniform float edge_darkening;
+uniform vec3 color;
+// Which faces were on the original cube, in bits: +x, -x, +y, -y, +z, -z
+uniform int outer_faces;
+// Drawn on faces cut when the cube split, if has_inner_color is set
+uniform bool has_inner_color;
+uniform vec3 inner_color;
+// Colors are linear, and are encoded as sRGB on the way out
+uniform bool srgb_output;
+uniform sampler2D tex;
+uniform sampler2D shadow_map;
 
 // Source: http://lolengine.net/blog/2013/07/27/rgb-to-hsv-in-glsl
 vec3 hsv2rgb(vec3 c)
@@ -16,17 +39,123 @@ vec3 hsv2rgb(vec3 c)
     return c.z * mix(K.xxx, clamp(p - K.xxx, 0.0, 1.0), c.y);
 }
 
+// The sRGB transfer functions, per channel
+vec3 linear_to_srgb(vec3 c) {
+    vec3 low = c * 12.92;
+    vec3 high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, step(vec3(0.0031308), c));
+}
+
+vec3 srgb_to_linear(vec3 c) {
+    vec3 low = c / 12.92;
+    vec3 high = pow((c + 0.055) / 1.055, vec3(2.4));
+    return mix(low, high, step(vec3(0.04045), c));
+}
+
 bool is_edge(float x) {
     float m = 0.005/cube_size;
     return (x < m) || (x > (1.0-m));
 }
 
+// How far the fragment is from the nearest edge of its face, from 0 (on an
+// edge) to 0.5 (the middle), in units of the subcube's side.
+float edge_distance() {
+    vec3 n = abs(face_normal);
+    vec2 uv;
+    if (n.x > 0.5) {
+        uv = npos.zy;
+    } else if (n.y > 0.5) {
+        uv = npos.xz;
+    } else {
+        uv = npos.xy;
+    }
+    vec2 d = min(uv, 1.0 - uv);
+    return min(d.x, d.y);
+}
+
+// True if something nearer the light covers this fragment in the shadow map.
+bool in_shadow() {
+    vec3 p = light_space_pos.xyz / light_space_pos.w * 0.5 + 0.5;
+    if (any(lessThan(p, vec3(0.0))) || any(greaterThan(p, vec3(1.0)))) {
+        return false;
+    }
+    // Keeps faces from shadowing themselves
+    float bias = 0.002;
+    return p.z - bias > texture2D(shadow_map, p.xy).r;
+}
+
+// Where the fragment lies on the original cube's face, from 0 to 1.
+// Shards share the mapping of the cube they came from.
+vec2 face_uv() {
+    vec3 n = abs(face_normal);
+    vec2 uv;
+    if (n.x > 0.5) {
+        uv = fragment_coord.zy;
+    } else if (n.y > 0.5) {
+        uv = fragment_coord.xz;
+    } else {
+        uv = fragment_coord.xy;
+    }
+    // Images are stored top row first
+    return vec2(uv.x, 1.0 - uv.y);
+}
+
+// True if the fragment's face was on the outside of the original cube.
+bool is_outer_face() {
+    vec3 n = abs(face_normal);
+    float face;
+    if (n.x > 0.5) {
+        face = face_normal.x > 0.0 ? 0.0 : 1.0;
+    } else if (n.y > 0.5) {
+        face = face_normal.y > 0.0 ? 2.0 : 3.0;
+    } else {
+        face = face_normal.z > 0.0 ? 4.0 : 5.0;
+    }
+    // No bitwise operators in GLSL ES 1.0
+    return mod(floor(float(outer_faces) / exp2(face)), 2.0) > 0.5;
+}
+
 void main(void) {
+    if (shadow) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, alpha);
+        return;
+    }
+
     // Hue: 0.0 to 1.0
     // Saturation: 0.0 to 1.0
     // Lightness: 0.5 to 1.0
     vec3 hsv = mix(vec3(0.0, 0.0, 0.25), vec3(1.0, 1.0, 1.0), fragment_coord);
     vec3 rgb = hsv2rgb(hsv);
+    if (has_color) {
+        rgb = color;
+    }
+    bool inner = has_inner_color && !is_outer_face();
+    if (inner) {
+        rgb = inner_color;
+    }
+
+    float a = alpha;
+    if (textured && !inner) {
+        vec4 texel = texture2D(tex, face_uv());
+        // Fully transparent texels would still hide what's behind them in the depth buffer
+        if (texel.a < 0.01) {
+            discard;
+        }
+        // Images are stored as sRGB
+        rgb = srgb_output ? srgb_to_linear(texel.rgb) : texel.rgb;
+        a *= texel.a;
+    }
+
+    if (shading_mode != 0) {
+        // Blue (low) to red (high)
+        float t = clamp(shade_value, 0.0, 1.0);
+        rgb = hsv2rgb(vec3((1.0 - t) * 0.66, 1.0, 1.0));
+    }
+
+    if (edge_darkening > 0.0) {
+        // Falls off over the outer fifth of the face, like a bevel catching less light
+        rgb *= 1.0 - edge_darkening * (1.0 - smoothstep(0.0, 0.2, edge_distance()));
+    }
 
     if (show_outlines) {
         bool e_x = is_edge(npos.x);
@@ -38,9 +167,25 @@ void main(void) {
         }
     }
 
-    if (hovered) {
-        // tint red if hovered
-        rgb = mix(vec3(0.9, 0.25, 0.25), vec3(1.0), rgb);
+    if (shadow_mapped && in_shadow()) {
+        rgb = rgb * 0.5;
+    }
+
+    if (highlight != 0) {
+        // tint red if hovered, blue if selected, orange while drilling, and
+        // yellow while a fuse burns
+        vec3 tint = vec3(0.9, 0.25, 0.25);
+        if (highlight == 2) {
+            tint = vec3(0.3, 0.5, 1.0);
+        } else if (highlight == 3) {
+            tint = vec3(1.0, 0.6, 0.1);
+        } else if (highlight == 4) {
+            tint = vec3(1.0, 0.95, 0.3);
+        }
+        rgb = mix(tint, vec3(1.0), rgb);
+    }
+    if (srgb_output) {
+        rgb = linear_to_srgb(clamp(rgb, 0.0, 1.0));
     }
-    gl_FragColor = vec4(rgb, 1.0);
+    gl_FragColor = vec4(rgb, a);
 }
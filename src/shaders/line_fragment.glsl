@@ -0,0 +1,19 @@
+#version 100
+precision highp float;
+
+varying lowp vec4 line_color;
+
+// As in fragment.glsl
+uniform bool srgb_output;
+
+// Copied from fragment.glsl. See point_fragment.glsl for why
+vec3 linear_to_srgb(vec3 c) {
+    vec3 low = c * 12.92;
+    vec3 high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, step(vec3(0.0031308), c));
+}
+
+void main(void) {
+    vec3 rgb = clamp(line_color.rgb, 0.0, 1.0);
+    gl_FragColor = vec4(srgb_output ? linear_to_srgb(rgb) : rgb, line_color.a);
+}
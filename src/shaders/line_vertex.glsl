@@ -0,0 +1,14 @@
+#version 100
+precision highp float;
+
+attribute vec3 position;
+attribute vec4 color;
+
+varying lowp vec4 line_color;
+
+uniform mat4 projection_view;
+
+void main(void) {
+    line_color = color;
+    gl_Position = projection_view * vec4(position, 1.0);
+}
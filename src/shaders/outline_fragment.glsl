@@ -0,0 +1,51 @@
+#version 100
+precision highp float;
+
+varying vec2 uv;
+
+uniform sampler2D color_tex;
+uniform sampler2D depth_tex;
+uniform vec2 texel_size;
+uniform float near;
+uniform float far;
+// Orthographic depth is already linear in distance
+uniform bool orthographic;
+
+// Distance from the camera at a point on the screen
+float linear_depth(vec2 offset) {
+    float z = texture2D(depth_tex, uv + offset * texel_size).r * 2.0 - 1.0;
+    if (orthographic) {
+        return near + (z * 0.5 + 0.5) * (far - near);
+    }
+    return 2.0 * near * far / (far + near - z * (far - near));
+}
+
+void main(void) {
+    float tl = linear_depth(vec2(-1.0,  1.0));
+    float t  = linear_depth(vec2( 0.0,  1.0));
+    float tr = linear_depth(vec2( 1.0,  1.0));
+    float l  = linear_depth(vec2(-1.0,  0.0));
+    float c  = linear_depth(vec2( 0.0,  0.0));
+    float r  = linear_depth(vec2( 1.0,  0.0));
+    float bl = linear_depth(vec2(-1.0, -1.0));
+    float b  = linear_depth(vec2( 0.0, -1.0));
+    float br = linear_depth(vec2( 1.0, -1.0));
+
+    // Sobel: where the depth jumps, i.e. the silhouettes of subcubes
+    float gx = (tr + 2.0 * r + br) - (tl + 2.0 * l + bl);
+    float gy = (tl + 2.0 * t + tr) - (bl + 2.0 * b + br);
+    float jump = length(vec2(gx, gy)) / c;
+
+    // Laplacian of 1/depth: zero across any flat face, so it only picks up
+    // where the slope changes, i.e. the creases between faces. Orthographic
+    // depth itself is what's flat across a face.
+    float crease = orthographic
+        ? abs(l + r + t + b - 4.0 * c) / c
+        : abs(1.0/l + 1.0/r + 1.0/t + 1.0/b - 4.0/c) * c;
+
+    float edge = max(smoothstep(0.05, 0.1, jump), smoothstep(0.001, 0.003, crease));
+
+    vec3 rgb = texture2D(color_tex, uv).rgb;
+    // The same darkening as the per-subcube outlines
+    gl_FragColor = vec4(rgb * (1.0 - 0.5 * edge), 1.0);
+}
@@ -0,0 +1,26 @@
+#version 100
+precision highp float;
+
+varying lowp vec4 point_color;
+
+// As in fragment.glsl
+uniform bool srgb_output;
+
+// Copied from fragment.glsl, and into line_fragment.glsl. GLSL ES 1.00 has
+// no #include, and fragment.glsl is also loaded as it is from
+// CUBES_SHADER_DIR, so there's nowhere to keep a single copy.
+vec3 linear_to_srgb(vec3 c) {
+    vec3 low = c * 12.92;
+    vec3 high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, step(vec3(0.0031308), c));
+}
+
+void main(void) {
+    // Round off the square each point is drawn as
+    vec2 d = gl_PointCoord - 0.5;
+    if (dot(d, d) > 0.25) {
+        discard;
+    }
+    vec3 rgb = clamp(point_color.rgb, 0.0, 1.0);
+    gl_FragColor = vec4(srgb_output ? linear_to_srgb(rgb) : rgb, point_color.a);
+}
@@ -0,0 +1,19 @@
+#version 100
+precision highp float;
+
+attribute vec3 position;
+// The subcube's length, in world units
+attribute float size;
+attribute vec4 color;
+
+varying lowp vec4 point_color;
+
+uniform mat4 projection_view;
+// Pixels per world unit at a distance of 1 from the camera
+uniform float point_scale;
+
+void main(void) {
+    point_color = color;
+    gl_Position = projection_view * vec4(position, 1.0);
+    gl_PointSize = max(size * point_scale / gl_Position.w, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 100
+precision highp float;
+
+varying vec2 uv;
+
+uniform sampler2D color_tex;
+// How much of the image to blend over what's already drawn
+uniform float opacity;
+
+void main(void) {
+    gl_FragColor = vec4(texture2D(color_tex, uv).rgb, opacity);
+}
@@ -2,11 +2,15 @@
 precision highp float;
 
 attribute vec3 position;
+attribute vec3 normal;
 varying vec3 fragment_coord;
 varying vec3 npos;
+varying vec3 face_normal;
+varying vec4 light_space_pos;
 
 uniform mat4 projection_view;
 uniform mat4 model;
+uniform mat4 light_projection_view;
 
 uniform vec3 cube_pos;
 uniform float cube_size;
@@ -19,5 +23,7 @@ void main(void) {
     npos = position + 0.5;
     // range from cube_pos to cube_pos+cube_size
     fragment_coord = mix(cube_pos, cube_pos+cube_size, npos);
+    face_normal = normal;
+    light_space_pos = light_projection_view * model * v;
     gl_Position = p;
 }
@@ -0,0 +1,235 @@
+/// A source of monotonic time, in seconds since an arbitrary epoch.
+pub trait Clock {
+    fn now(&self) -> f64;
+}
+
+/// The platform's clock.
+///
+/// Natively this is `std::time::Instant`. On wasm, where `Instant` isn't
+/// available, it's the browser's clock as exposed by miniquad.
+pub struct SystemClock {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now()
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn now(&self) -> f64 {
+        miniquad::date::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Default)]
+pub struct FakeClock {
+    time: f64
+}
+
+impl FakeClock {
+    pub fn new() -> FakeClock { Default::default() }
+
+    pub fn advance(&mut self, seconds: f64) {
+        self.time += seconds;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> f64 {
+        self.time
+    }
+}
+
+/// Converts elapsed clock time into a number of fixed-length steps.
+///
+/// Time that doesn't add up to a whole step is carried over to the next call.
+pub struct FixedTimestep {
+    /// The most steps a single call will ask for. After a long stall, the
+    /// time beyond this is dropped, so the simulation doesn't fall further
+    /// behind trying to catch up.
+    pub max_steps: u32,
+    step_length: f64,
+    last_time: Option<f64>,
+    step_error: f64,
+    dropped: f64
+}
+
+impl FixedTimestep {
+    pub fn new(steps_per_second: u32) -> FixedTimestep {
+        FixedTimestep {
+            // A quarter of a second
+            max_steps: (steps_per_second / 4).max(1),
+            step_length: 1.0 / steps_per_second as f64,
+            last_time: None,
+            step_error: 0.0,
+            dropped: 0.0
+        }
+    }
+
+    /// Get how many steps to run to catch up with the clock, up to `max_steps`.
+    /// The first call always returns 1.
+    pub fn steps(&mut self, clock: &dyn Clock) -> u32 {
+        self.dropped = 0.0;
+
+        let now = clock.now();
+        let Some(last_time) = self.last_time.replace(now) else {
+            return 1;
+        };
+
+        self.step_error += now - last_time;
+
+        let mut steps = 0;
+        while self.step_error >= self.step_length {
+            if steps == self.max_steps {
+                self.dropped = self.step_error;
+                self.step_error = 0.0;
+                break;
+            }
+            self.step_error -= self.step_length;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Seconds the last call to `steps` dropped for going over `max_steps`.
+    pub fn dropped(&self) -> f64 {
+        self.dropped
+    }
+}
+
+/// The durations of the most recent frames, for percentiles over a sliding window.
+pub struct FrameStats {
+    durations: Vec<f64>,
+    capacity: usize,
+    /// Where the next duration goes once the window is full
+    next: usize,
+    last_time: Option<f64>
+}
+
+impl FrameStats {
+    /// Keep the durations of the last `capacity` frames.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> FrameStats {
+        assert!(capacity > 0);
+
+        FrameStats {
+            durations: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+            last_time: None
+        }
+    }
+
+    /// Record a frame ending now, timed from the last call.
+    /// The first call only starts timing.
+    pub fn frame(&mut self, clock: &dyn Clock) {
+        let now = clock.now();
+        if let Some(last_time) = self.last_time.replace(now) {
+            self.record(now - last_time);
+        }
+    }
+
+    /// Record a frame that took `seconds`, replacing the oldest one if the window is full.
+    pub fn record(&mut self, seconds: f64) {
+        if self.durations.len() < self.capacity {
+            self.durations.push(seconds);
+        } else {
+            self.durations[self.next] = seconds;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.durations.is_empty()
+    }
+
+    /// The longest frame time among the fastest `p` percent of frames in the window,
+    /// by nearest rank. None if no frames have been recorded.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        if self.durations.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.durations.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.clamp(1, sorted.len()) - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_stall_runs_at_most_max_steps_then_recovers() {
+        let mut clock = FakeClock::new();
+        let mut timestep = FixedTimestep::new(60);
+        assert_eq!(timestep.steps(&clock), 1);
+
+        clock.advance(2.0);
+        assert_eq!(timestep.steps(&clock), timestep.max_steps);
+        assert!(timestep.dropped() > 1.0);
+
+        // Back to one step per frame, with nothing left to catch up on
+        clock.advance(1.0 / 60.0 + 1e-9);
+        assert_eq!(timestep.steps(&clock), 1);
+        assert_eq!(timestep.dropped(), 0.0);
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let mut stats = FrameStats::new(100);
+        assert_eq!(stats.percentile(50.0), None);
+
+        // Shuffled, so the order they're recorded in doesn't matter
+        for ms in (1..=100).map(|i| (i * 37) % 100 + 1) {
+            stats.record(ms as f64 / 1000.0);
+        }
+        let ms = |p| (stats.percentile(p).unwrap() * 1000.0).round();
+        assert_eq!([ms(0.0), ms(50.0), ms(95.0), ms(99.0), ms(100.0)], [1.0, 50.0, 95.0, 99.0, 100.0]);
+    }
+
+    #[test]
+    fn frame_stats_only_keep_the_window() {
+        let mut clock = FakeClock::new();
+        let mut stats = FrameStats::new(3);
+
+        stats.frame(&clock);
+        assert!(stats.is_empty());
+        for seconds in [0.5, 0.1, 0.1, 0.1] {
+            clock.advance(seconds);
+            stats.frame(&clock);
+        }
+
+        // The slow first frame has been pushed out
+        assert_eq!(stats.len(), 3);
+        assert!((stats.percentile(100.0).unwrap() - 0.1).abs() < 1e-9);
+    }
+}
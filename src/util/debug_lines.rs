@@ -0,0 +1,132 @@
+//! Line segments collected over a frame, for debug overlays.
+
+use cgmath::{Point3, Vector3};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DebugSegment {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    /// RGBA, from 0 to 1
+    pub color: [f32; 4]
+}
+
+/// Segments pushed by whatever wants them drawn this frame.
+///
+/// The renderer draws them all after the main pass, then clears them.
+#[derive(Default)]
+pub struct DebugLines {
+    segments: Vec<DebugSegment>
+}
+
+impl DebugLines {
+    pub fn new() -> DebugLines {
+        DebugLines { segments: Vec::new() }
+    }
+
+    pub fn push(&mut self, start: Point3<f32>, end: Point3<f32>, color: [f32; 4]) {
+        self.segments.push(DebugSegment { start, end, color });
+    }
+
+    /// Push a small cross centered on `point`, one segment along each axis.
+    pub fn cross(&mut self, point: Point3<f32>, size: f32, color: [f32; 4]) {
+        for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            let v = axis * (size / 2.0);
+            self.push(point - v, point + v, color);
+        }
+    }
+
+    /// Push `n` in seven-segment digits, centered on `center`.
+    /// Each digit is `right` wide and `up` tall, so facing them toward the
+    /// camera makes a billboard.
+    pub fn number(&mut self, n: usize, center: Point3<f32>, right: Vector3<f32>, up: Vector3<f32>, color: [f32; 4]) {
+        // Which of the segments in SEGMENTS each digit lights up
+        const DIGITS: [u8; 10] = [
+            0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110,
+            0b1101101, 0b1111101, 0b0000111, 0b1111111, 0b1101111
+        ];
+        // From the top, clockwise around the outside, then the middle.
+        // In units of the digit's width and height, from its bottom-left corner.
+        const SEGMENTS: [((f32, f32), (f32, f32)); 7] = [
+            ((0.0, 1.0), (1.0, 1.0)),
+            ((1.0, 1.0), (1.0, 0.5)),
+            ((1.0, 0.5), (1.0, 0.0)),
+            ((1.0, 0.0), (0.0, 0.0)),
+            ((0.0, 0.0), (0.0, 0.5)),
+            ((0.0, 0.5), (0.0, 1.0)),
+            ((0.0, 0.5), (1.0, 0.5))
+        ];
+        // Between digits, in digit widths
+        const SPACING: f32 = 0.5;
+
+        let digits = n.to_string();
+        let width = digits.len() as f32 * (1.0 + SPACING) - SPACING;
+        let bottom_left = center - right * (width / 2.0) - up * 0.5;
+
+        for (i, digit) in digits.bytes().enumerate() {
+            let corner = bottom_left + right * (i as f32 * (1.0 + SPACING));
+            let lit = DIGITS[(digit - b'0') as usize];
+            for (bit, &((x0, y0), (x1, y1))) in SEGMENTS.iter().enumerate() {
+                if lit & (1 << bit) != 0 {
+                    self.push(corner + right * x0 + up * y0, corner + right * x1 + up * y1, color);
+                }
+            }
+        }
+    }
+
+    pub fn segments(&self) -> &[DebugSegment] {
+        &self.segments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::EuclideanSpace;
+
+    #[test]
+    fn a_cross_is_three_segments_through_the_point() {
+        let mut lines = DebugLines::new();
+        let point = Point3::new(1.0, 2.0, 3.0);
+        lines.cross(point, 0.5, [1.0; 4]);
+
+        assert_eq!(lines.segments().len(), 3);
+        for segment in lines.segments() {
+            let v = segment.end - segment.start;
+            assert_eq!(segment.start.midpoint(segment.end), point);
+            assert_eq!(v.x.abs() + v.y.abs() + v.z.abs(), 0.5);
+        }
+
+        lines.clear();
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn numbers_are_drawn_in_seven_segment_digits() {
+        let mut lines = DebugLines::new();
+        let (right, up) = (Vector3::unit_x(), Vector3::unit_y());
+
+        lines.number(8, Point3::origin(), right, up, [1.0; 4]);
+        assert_eq!(lines.segments().len(), 7);
+        // The 8 fills its whole box, centered on the origin
+        for segment in lines.segments() {
+            for p in [segment.start, segment.end] {
+                assert!(p.x.abs() <= 0.5 && p.y.abs() <= 0.5);
+            }
+        }
+
+        lines.clear();
+        lines.number(10, Point3::origin(), right, up, [1.0; 4]);
+        assert_eq!(lines.segments().len(), 2 + 6);
+        // The 1 is on the left, and the 0 on the right
+        assert!(lines.segments()[..2].iter().all(|s| s.start.x < 0.0));
+        assert!(lines.segments()[2..].iter().all(|s| s.start.x > 0.0 || s.end.x > 0.0));
+    }
+}
@@ -1,4 +1,4 @@
-use cgmath::{Matrix3, Matrix4, Vector3, Quaternion, Rad, SquareMatrix, BaseFloat};
+use cgmath::{Matrix3, Matrix4, Vector3, Vector4, Quaternion, Rad, SquareMatrix, BaseFloat, InnerSpace};
 
 pub trait MatrixBuilder<S: Copy, V, Q>: Sized {
     fn scale(&self, x: S, y: S, z: S) -> Self;
@@ -9,6 +9,9 @@ pub trait MatrixBuilder<S: Copy, V, Q>: Sized {
     fn quaternion(&self, value: &Q) -> Self;
     fn translate(&self, x: S, y: S, z: S) -> Self;
     fn translate_v(&self, disp: &V) -> Self;
+    /// Flatten everything onto a plane, along the lines from a point light.
+    /// The plane holds the points `p` where `dot(normal, p) + distance == 0`.
+    fn project_to_plane(&self, normal: &V, distance: S, light_pos: &V) -> Self;
 
     fn scale_s(&self, value: S) -> Self {
         self.scale(value, value, value)
@@ -55,4 +58,34 @@ impl<S: BaseFloat> MatrixBuilder<S, Vector3<S>, Quaternion<S>> for Matrix4<S> {
         self * Matrix4::from_translation(*disp)
     }
 
+    fn project_to_plane(&self, normal: &Vector3<S>, distance: S, light_pos: &Vector3<S>) -> Matrix4<S> {
+        let plane = normal.extend(distance);
+        let light = light_pos.extend(S::one());
+        let d = plane.dot(light);
+
+        // d * I - light * plane^T
+        let col = |i: usize, e: Vector4<S>| e * d - light * plane[i];
+        self * Matrix4::from_cols(
+            col(0, Vector4::unit_x()),
+            col(1, Vector4::unit_y()),
+            col(2, Vector4::unit_z()),
+            col(3, Vector4::unit_w())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Point3, Transform};
+
+    #[test]
+    fn project_to_plane_lands_on_the_plane_in_line_with_the_light() {
+        let light = Vector3::new(0.0, 4.0, 0.0);
+        let m = Matrix4::identity().project_to_plane(&Vector3::unit_y(), 1.0, &light);
+
+        // Halfway between the light and y = -1, and one unit out
+        let p = m.transform_point(Point3::new(1.0, 1.5, 0.0));
+        assert!((p - Point3::new(2.0, -1.0, 0.0)).magnitude() < 1e-5);
+    }
 }
@@ -1,2 +1,6 @@
 pub mod matrix;
 pub mod compare;
+pub mod clock;
+pub mod texture;
+pub mod on_change;
+pub mod debug_lines;
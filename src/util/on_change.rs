@@ -0,0 +1,27 @@
+/// Remembers a value and reports when it changes.
+pub struct ValueOnChange<T> {
+    value: Option<T>
+}
+
+impl<T: PartialEq> ValueOnChange<T> {
+    pub fn new() -> ValueOnChange<T> {
+        ValueOnChange { value: None }
+    }
+
+    /// Store the value. Returns it if it differs from the previous one,
+    /// or if it's the first.
+    pub fn set(&mut self, value: T) -> Option<&T> {
+        if self.value.as_ref() == Some(&value) {
+            None
+        } else {
+            self.value = Some(value);
+            self.value.as_ref()
+        }
+    }
+}
+
+impl<T: PartialEq> Default for ValueOnChange<T> {
+    fn default() -> ValueOnChange<T> {
+        ValueOnChange::new()
+    }
+}
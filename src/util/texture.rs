@@ -0,0 +1,139 @@
+/// A decoded image with 8-bit RGBA pixels, ready to be uploaded to the GPU.
+pub struct Texture2D {
+    pub width: u32,
+    pub height: u32,
+    /// Rows of pixels, top row first
+    pub rgba: Vec<u8>,
+    /// If true, the source image had an alpha channel.
+    /// Otherwise every pixel is opaque.
+    pub has_alpha: bool
+}
+
+impl Texture2D {
+    pub fn from_file(path: &str) -> Result<Texture2D, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Texture2D::from_png_bytes(&bytes)
+    }
+
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Texture2D, String> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut buf = vec![0; reader.output_buffer_size().ok_or("The image is too large")?];
+        let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+        let pixels = &buf[..info.buffer_size()];
+
+        let rgba: Vec<u8> = match info.color_type {
+            png::ColorType::Rgba => pixels.to_vec(),
+            png::ColorType::Rgb => pixels.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => pixels.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => pixels.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+            png::ColorType::Indexed => return Err("Indexed images should have been expanded".to_string())
+        };
+
+        let has_alpha = matches!(info.color_type, png::ColorType::Rgba | png::ColorType::GrayscaleAlpha);
+
+        Ok(Texture2D {
+            width: info.width,
+            height: info.height,
+            rgba,
+            has_alpha
+        })
+    }
+
+    /// Encode as an 8-bit RGBA PNG.
+    pub fn to_png_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&self.rgba).map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let bytes = self.to_png_bytes()?;
+        std::fs::write(path, bytes).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Texture2D;
+
+    fn encode_png(width: u32, height: u32, color_type: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn rgb_png_gains_opaque_alpha() {
+        let rgb = [
+            255, 0, 0,   0, 255, 0,
+            0, 0, 255,   10, 20, 30
+        ];
+        let texture = Texture2D::from_png_bytes(&encode_png(2, 2, png::ColorType::Rgb, &rgb)).unwrap();
+
+        assert_eq!((texture.width, texture.height), (2, 2));
+        assert!(!texture.has_alpha);
+        assert_eq!(texture.rgba, vec![
+            255, 0, 0, 255,   0, 255, 0, 255,
+            0, 0, 255, 255,   10, 20, 30, 255
+        ]);
+    }
+
+    #[test]
+    fn rgba_png_keeps_alpha() {
+        let rgba = [255, 0, 0, 0,   0, 255, 0, 128];
+        let texture = Texture2D::from_png_bytes(&encode_png(2, 1, png::ColorType::Rgba, &rgba)).unwrap();
+
+        assert!(texture.has_alpha);
+        assert_eq!(texture.rgba, rgba);
+    }
+
+    #[test]
+    fn grayscale_png_is_expanded() {
+        let texture = Texture2D::from_png_bytes(&encode_png(3, 1, png::ColorType::Grayscale, &[0, 128, 255])).unwrap();
+
+        assert_eq!((texture.width, texture.height), (3, 1));
+        assert_eq!(texture.rgba, vec![0, 0, 0, 255, 128, 128, 128, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn from_file_reads_dimensions() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/screenshots/screenshot1.png");
+        let texture = Texture2D::from_file(path).unwrap();
+
+        assert_eq!((texture.width, texture.height), (1680, 987));
+        assert_eq!(texture.rgba.len(), 1680 * 987 * 4);
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let texture = Texture2D {
+            width: 2,
+            height: 1,
+            rgba: vec![255, 0, 0, 255,   0, 255, 0, 128],
+            has_alpha: true
+        };
+        let decoded = Texture2D::from_png_bytes(&texture.to_png_bytes().unwrap()).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (2, 1));
+        assert_eq!(decoded.rgba, texture.rgba);
+
+        let short = Texture2D { rgba: vec![0; 4], ..texture };
+        assert!(short.to_png_bytes().is_err());
+    }
+
+    #[test]
+    fn invalid_data_is_an_error() {
+        assert!(Texture2D::from_png_bytes(b"not a png").is_err());
+        assert!(Texture2D::from_file("does/not/exist.png").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Picking through the camera's projection, from pointer coordinates to subcubes.
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Vector3};
+
+use cubes::game::cube::CubeStateKind;
+use cubes::game::{GameInput, GameState};
+
+const VIEWPORT: (i32, i32) = (1920, 1080);
+
+/// The subcube under the pointer, if any.
+fn pick(state: &GameState, pointer: (f32, f32)) -> Option<usize> {
+    let projection_view = state.solve_projection_view(VIEWPORT);
+    let ray = state.solve_pointer_ray(projection_view, Some(pointer))?;
+    state.cube.get_subcube_from_ray(&ray).map(|(index, _)| index)
+}
+
+/// Where a point lands in normalized device coordinates.
+fn project(projection_view: Matrix4<f32>, point: Vector3<f32>) -> (f32, f32) {
+    let clip = projection_view * point.extend(1.0);
+    (clip.x / clip.w, clip.y / clip.w)
+}
+
+#[test]
+fn the_center_picks_the_cube() {
+    let state = GameState::new();
+    assert_eq!(pick(&state, (0.0, 0.0)), Some(0));
+}
+
+#[test]
+fn a_step_reports_the_picked_subcube() {
+    let mut state = GameState::new();
+    let result = state.step(VIEWPORT, &GameInput { pointer: Some((0.0, 0.0)), ..GameInput::new() });
+    assert_eq!(result.selected_subcube, Some(0));
+}
+
+#[test]
+fn the_corners_miss() {
+    let state = GameState::new();
+    for pointer in [(-0.95, -0.95), (0.95, -0.95), (-0.95, 0.95), (0.95, 0.95)] {
+        assert_eq!(pick(&state, pointer), None, "pointer at {:?}", pointer);
+    }
+}
+
+#[test]
+fn the_nearest_shard_is_picked_where_it_is_drawn() {
+    let state = GameState::new_with_subdivision(2);
+    let projection_view = state.solve_projection_view(VIEWPORT);
+
+    // Nothing can be in front of the shard closest to the camera
+    let depth = |pos: Vector3<f32>| (projection_view * pos.extend(1.0)).w;
+    let (nearest, subcube) = state.cube.subcubes.iter().enumerate()
+        .min_by(|(_, a), (_, b)| depth(a.pos).total_cmp(&depth(b.pos)))
+        .unwrap();
+
+    assert_eq!(pick(&state, project(projection_view, subcube.pos)), Some(nearest));
+}
+
+#[test]
+fn coincident_subcubes_pick_the_lowest_index() {
+    let mut state = GameState::new_with_subdivision(2);
+    let projection_view = state.solve_projection_view(VIEWPORT);
+
+    let depth = |pos: Vector3<f32>| (projection_view * pos.extend(1.0)).w;
+    let nearest = *state.cube.subcubes.iter()
+        .min_by(|a, b| depth(a.pos).total_cmp(&depth(b.pos)))
+        .unwrap();
+
+    // Three subcubes exactly where the nearest is: at the start, in the middle and at the end
+    state.cube.subcubes.insert(0, nearest);
+    state.cube.subcubes.push(nearest);
+    assert_eq!(pick(&state, project(projection_view, nearest.pos)), Some(0));
+
+    state.cube.subcubes.remove(0);
+    let middle = state.cube.subcubes.iter().position(|s| *s == nearest).unwrap();
+    assert_eq!(pick(&state, project(projection_view, nearest.pos)), Some(middle));
+}
+
+#[test]
+fn unpickable_subcubes_let_rays_through() {
+    let mut state = GameState::new_with_subdivision(2);
+    let projection_view = state.solve_projection_view(VIEWPORT);
+    let front = pick(&state, (0.0, 0.0)).unwrap();
+    let hits = |state: &GameState| {
+        let ray = state.solve_pointer_ray(projection_view, Some((0.0, 0.0))).unwrap();
+        state.cube.get_subcubes_from_ray(&ray).len()
+    };
+
+    state.cube.subcubes[front].pickable = false;
+    let behind = pick(&state, (0.0, 0.0));
+    assert!(behind.is_some() && behind != Some(front));
+    let through = hits(&state);
+
+    // Still unpickable once a rearrange puts everything back
+    state.cube.try_hurl_all(4.0);
+    state.cube.try_rearrange();
+    while state.cube.state_kind() != CubeStateKind::Simulating {
+        state.cube.step(1.0 / GameState::steps_per_second() as f32);
+    }
+    assert!(!state.cube.subcubes[front].pickable);
+    assert_eq!(pick(&state, (0.0, 0.0)), behind);
+
+    state.cube.pick_unpickable = true;
+    assert_eq!(pick(&state, (0.0, 0.0)), Some(front));
+    assert_eq!(hits(&state), through + 1);
+}
+
+#[test]
+fn picking_follows_a_stretched_subcube() {
+    use cgmath::Point3;
+    use collision::Ray3;
+
+    let mut state = GameState::new();
+    let ray = Ray3::new(Point3::new(1.2, 0.0, 5.0), -Vector3::unit_z());
+    assert!(state.cube.get_subcube_hit_from_ray(&ray).is_none());
+
+    // Three times as wide, and half as deep
+    state.cube.subcubes[0].scale = Vector3::new(3.0, 1.0, 0.5);
+    let (index, hit) = state.cube.get_subcube_hit_from_ray(&ray).unwrap();
+    assert_eq!(index, 0);
+    assert!((hit - Point3::new(1.2, 0.0, 0.25)).magnitude() < 1e-5);
+}
+
+#[test]
+fn the_camera_looks_at_what_the_center_picks() {
+    let state = GameState::new();
+    let projection_view = state.solve_projection_view(VIEWPORT);
+    let ray = state.solve_pointer_ray(projection_view, Some((0.0, 0.0))).unwrap();
+
+    let forward = state.camera_forward();
+    assert!((ray.direction - forward).magnitude() < 1e-4, "{:?} != {:?}", ray.direction, forward);
+}
+
+#[test]
+fn camera_up_points_up_the_screen() {
+    let state = GameState::new();
+    let projection_view = state.solve_projection_view(VIEWPORT);
+
+    let up = state.camera_up();
+    assert!(up.dot(state.camera_forward()).abs() < 1e-5);
+    let (x, y) = project(projection_view, up * 0.1);
+    assert!(x.abs() < 1e-4 && y > 0.0, "{:?}", (x, y));
+}
+
+#[test]
+fn zooming_keeps_the_point_under_the_pointer() {
+    let mut state = GameState::new();
+    state.idle_spin = None;
+    state.orbit_inertia = false;
+
+    let pointer = (0.1, -0.05);
+    let projection_view = state.solve_projection_view(VIEWPORT);
+    let ray = state.solve_pointer_ray(projection_view, Some(pointer)).unwrap();
+    let (_, anchor) = state.cube.get_subcube_hit_from_ray(&ray).unwrap();
+
+    // Stop the starting spin, then zoom in and let it settle
+    state.step(VIEWPORT, &GameInput { release_view: true, ..GameInput::new() });
+    state.step(VIEWPORT, &GameInput { pointer: Some(pointer), zoom_view_change: 1.0, ..GameInput::new() });
+    for _ in 0..120 {
+        state.step(VIEWPORT, &GameInput::new());
+    }
+
+    let (x, y) = project(state.solve_projection_view(VIEWPORT), anchor.to_vec());
+    assert!((x - pointer.0).abs() < 1e-3 && (y - pointer.1).abs() < 1e-3, "{:?} moved to {:?}", pointer, (x, y));
+}
+
+#[test]
+fn the_reticle_picks_instead_of_the_pointer() {
+    let mut state = GameState::new();
+    let corner = Some((0.95, 0.95));
+
+    let result = state.step(VIEWPORT, &GameInput { pointer: corner, toggle_reticle: true, ..GameInput::new() });
+    assert_eq!(state.reticle, Some((0.0, 0.0)));
+    assert_eq!(result.selected_subcube, Some(0));
+
+    // Moved off the cube, it misses wherever the pointer is
+    let result = state.step(VIEWPORT, &GameInput { pointer: Some((0.0, 0.0)), move_reticle: (0.95, 0.95), ..GameInput::new() });
+    assert_eq!(result.selected_subcube, None);
+}
+
+#[test]
+fn a_pick_tolerance_catches_near_misses_of_tiny_shards() {
+    let mut state = GameState::new();
+    state.cube.subcubes[0].subcube_length = 0.01;
+    state.cube.mark_changed();
+    let near_miss = GameInput { pointer: Some((0.05, 0.0)), ..GameInput::new() };
+
+    assert_eq!(state.step(VIEWPORT, &near_miss).selected_subcube, None);
+    state.pick_tolerance = 0.1;
+    assert_eq!(state.step(VIEWPORT, &near_miss).selected_subcube, Some(0));
+
+    // Still nothing well away from it
+    let far = GameInput { pointer: Some((0.9, 0.0)), ..GameInput::new() };
+    assert_eq!(state.step(VIEWPORT, &far).selected_subcube, None);
+}
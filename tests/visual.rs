@@ -0,0 +1,106 @@
+//! Renders canonical scenes through the demo's `--render-to` and compares
+//! them with the references in `tests/fixtures`.
+//!
+//! These need a display to open a window on and a working OpenGL driver, so
+//! they're ignored by default, and skip themselves if the demo can't get an
+//! OpenGL context. On a headless machine a virtual display works, with
+//! Mesa's software renderer if there's no GPU:
+//!
+//! ```sh
+//! LIBGL_ALWAYS_SOFTWARE=1 xvfb-run cargo test --test visual -- --ignored
+//! ```
+//!
+//! Set `CUBES_BLESS=1` to save the renders as the new references instead of
+//! comparing against them.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use cubes::util::texture::Texture2D;
+
+const SIZE: (u32, u32) = (320, 240);
+/// How far apart a channel can be before the pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 8;
+/// The fraction of pixels that can be different, for edges rasterized a little differently
+const PIXEL_TOLERANCE: f64 = 0.001;
+
+/// What miniquad panics with when there's no display or OpenGL context to be had
+const NO_CONTEXT: &[&str] = &[
+    "XOpenDisplay() failed",
+    "GLX: ",
+    "eglMakeCurrent failed",
+    "surface creation failed"
+];
+
+/// Render with the demo binary, at a fixed size and seed.
+/// Returns None if the demo couldn't get an OpenGL context to render with.
+fn render(name: &str, args: &[&str]) -> Option<Texture2D> {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+    let (width, height) = (SIZE.0.to_string(), SIZE.1.to_string());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-cubes-demo"))
+        .args(["--width", &width, "--height", &height, "--seed", "1", "--vsync", "off"])
+        .arg("--render-to").arg(&path)
+        .args(args)
+        .env_remove("CUBES_TEXTURE")
+        .output()
+        .expect("Could not run the demo");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && NO_CONTEXT.iter().any(|message| stderr.contains(message)) {
+        return None;
+    }
+    assert!(output.status.success(), "The demo failed to render {}:\n{}", name, stderr);
+
+    Some(Texture2D::from_file(path.to_str().unwrap()).unwrap())
+}
+
+/// Render the scene and compare it with its reference, or replace the reference when blessing.
+fn check(name: &str, args: &[&str]) {
+    let Some(image) = render(name, args) else {
+        eprintln!("Skipping {}: there's no display or OpenGL context to render with", name);
+        return;
+    };
+    assert_eq!((image.width, image.height), SIZE);
+
+    let reference_path = format!("{}/tests/fixtures/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+    if std::env::var_os("CUBES_BLESS").is_some() {
+        image.save(&reference_path).unwrap();
+        return;
+    }
+
+    if !std::path::Path::new(&reference_path).exists() {
+        panic!(
+            "There's no reference for {} at {}. Make it with\n\n    \
+             CUBES_BLESS=1 cargo test --test visual -- --ignored\n\n\
+             then look it over and commit it.",
+            name, reference_path
+        );
+    }
+    let reference = Texture2D::from_file(&reference_path).unwrap();
+    assert_eq!((reference.width, reference.height), SIZE, "{} is the wrong size", reference_path);
+
+    let different = image.rgba.chunks_exact(4).zip(reference.rgba.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(&a, &b)| a.abs_diff(b) > CHANNEL_TOLERANCE))
+        .count();
+    let allowed = (PIXEL_TOLERANCE * (SIZE.0 * SIZE.1) as f64) as usize;
+    assert!(different <= allowed, "{} pixels of {} differ from {}", different, name, reference_path);
+}
+
+#[test]
+#[ignore = "needs a display and OpenGL"]
+fn fresh_cube() {
+    check("fresh_cube", &[]);
+}
+
+#[test]
+#[ignore = "needs a display and OpenGL"]
+fn subdivided_cube() {
+    check("subdivided_cube", &["--subdivide", "2"]);
+}
+
+#[test]
+#[ignore = "needs a display and OpenGL"]
+fn mid_rearrange() {
+    // The demo reel starts rearranging its debris 484 steps in
+    check("mid_rearrange", &["--demo", "--render-steps", "500"]);
+}